@@ -0,0 +1,330 @@
+//! Stylus VM hooks for native contract tests
+//!
+//! Contracts built with the Stylus SDK import their host functions from the
+//! VM. Linking this crate into a test binary supplies them natively, so
+//! public methods of a contract can be called straight from a test:
+//!
+//! - storage lives in the test's thread, so tests running side by side
+//!   never see each other's state; [`reset`] clears it for tests sharing
+//!   a thread
+//! - every call comes from [`CALLER`] to the contract under test at
+//!   [`CONTRACT`], in a block at [`TIMESTAMP`]. The SDK caches these values
+//!   for the whole process, so they cannot change between tests.
+//! - other contracts are stood in for by handlers registered with
+//!   [`mock_contract`]; calls to addresses without a handler revert with
+//!   no data, and every call made is recorded for [`calls_to`]
+//! - emitted logs are recorded for [`logs`]
+//!
+//! The hooks are only meant to be called by the SDK, which passes pointers
+//! valid for the sizes the VM defines for each of them.
+#![allow(clippy::missing_safety_doc)]
+
+// Imported packages
+use alloy_primitives::{address, Address, Keccak256, B256};
+use std::{cell::RefCell, collections::HashMap, slice};
+
+/// Address of the contract under test
+pub const CONTRACT: Address = address!("c0ffee254729296a45a3885639ac7e10f9d54979");
+
+/// Account every call to the contract under test comes from
+pub const CALLER: Address = address!("a11ce00000000000000000000000000000000001");
+
+/// Timestamp of the block every test runs in
+pub const TIMESTAMP: u64 = 1_700_000_000;
+
+/// Number of the block every test runs in
+pub const BLOCK_NUMBER: u64 = 1_000;
+
+/// Arbitrum One
+pub const CHAIN_ID: u64 = 42_161;
+
+/// Gas every call appears to have left
+const GAS_LEFT: u64 = 1 << 40;
+
+/// A mocked contract: takes calldata, returns data or revert data
+type Handler = Box<dyn FnMut(&[u8]) -> Result<Vec<u8>, Vec<u8>>>;
+
+/// A log as emitted: its topics and data
+pub type Log = (Vec<B256>, Vec<u8>);
+
+#[derive(Default)]
+struct Host {
+    storage: HashMap<B256, B256>,
+    contracts: HashMap<Address, Option<Handler>>,
+    calls: Vec<(Address, Vec<u8>)>,
+    logs: Vec<Log>,
+    return_data: Vec<u8>,
+}
+
+thread_local! {
+    static HOST: RefCell<Host> = RefCell::new(Host::default());
+}
+
+/// Clears storage, mocked contracts, recorded calls and logs
+pub fn reset() {
+    HOST.with(|host| *host.borrow_mut() = Host::default());
+}
+
+/// Stands `handler` in for the contract at `address`
+pub fn mock_contract(address: Address, handler: impl FnMut(&[u8]) -> Result<Vec<u8>, Vec<u8>> + 'static) {
+    HOST.with(|host| host.borrow_mut().contracts.insert(address, Some(Box::new(handler))));
+}
+
+/// Calldata of every call made to `address`, oldest first
+pub fn calls_to(address: Address) -> Vec<Vec<u8>> {
+    HOST.with(|host| host.borrow().calls.iter().filter(|(to, _)| *to == address).map(|(_, data)| data.clone()).collect())
+}
+
+/// Every log emitted, oldest first
+pub fn logs() -> Vec<Log> {
+    HOST.with(|host| host.borrow().logs.clone())
+}
+
+/// Runs the handler of `contract`, if any, and keeps what it returned
+fn dispatch(contract: *const u8, calldata: *const u8, calldata_len: usize, return_data_len: *mut usize) -> u8 {
+    let contract = Address::from_slice(unsafe { slice::from_raw_parts(contract, 20) });
+    let calldata = unsafe { slice::from_raw_parts(calldata, calldata_len) }.to_vec();
+
+    // Take the handler out while it runs, so it may use the host itself
+    let handler = HOST.with(|host| {
+        let mut host = host.borrow_mut();
+        host.calls.push((contract, calldata.clone()));
+        host.contracts.get_mut(&contract).and_then(Option::take)
+    });
+    let result = match handler {
+        Some(mut handler) => {
+            let result = handler(&calldata);
+            HOST.with(|host| host.borrow_mut().contracts.insert(contract, Some(handler)));
+            result
+        }
+        None => Err(Vec::new()),
+    };
+
+    let (status, data) = match result {
+        Ok(data) => (0, data),
+        Err(data) => (1, data),
+    };
+    unsafe { *return_data_len = data.len() };
+    HOST.with(|host| host.borrow_mut().return_data = data);
+    status
+}
+
+unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+    dest.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn account_balance(_address: *const u8, dest: *mut u8) {
+    write(dest, &[0; 32]);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn account_code(_address: *const u8, _offset: usize, _size: usize, _dest: *mut u8) -> usize {
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn account_code_size(address: *const u8) -> usize {
+    let address = Address::from_slice(slice::from_raw_parts(address, 20));
+    HOST.with(|host| usize::from(host.borrow().contracts.contains_key(&address)))
+}
+
+/// Mocked contracts hash to their address, every other account is empty
+#[no_mangle]
+pub unsafe extern "C" fn account_codehash(address: *const u8, dest: *mut u8) {
+    let address = Address::from_slice(slice::from_raw_parts(address, 20));
+    let hash = match HOST.with(|host| host.borrow().contracts.contains_key(&address)) {
+        true => address.into_word(),
+        false => B256::ZERO,
+    };
+    write(dest, hash.as_slice());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+    let key = B256::from_slice(slice::from_raw_parts(key, 32));
+    let value = HOST.with(|host| host.borrow().storage.get(&key).copied().unwrap_or_default());
+    write(dest, value.as_slice());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+    let key = B256::from_slice(slice::from_raw_parts(key, 32));
+    let value = B256::from_slice(slice::from_raw_parts(value, 32));
+    HOST.with(|host| host.borrow_mut().storage.insert(key, value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn storage_flush_cache(_clear: bool) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn block_basefee(basefee: *mut u8) {
+    write(basefee, &[0; 32]);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chainid() -> u64 {
+    CHAIN_ID
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn block_coinbase(coinbase: *mut u8) {
+    write(coinbase, &[0; 20]);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn block_gas_limit() -> u64 {
+    GAS_LEFT
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn block_number() -> u64 {
+    BLOCK_NUMBER
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn block_timestamp() -> u64 {
+    TIMESTAMP
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn call_contract(
+    contract: *const u8,
+    calldata: *const u8,
+    calldata_len: usize,
+    _value: *const u8,
+    _gas: u64,
+    return_data_len: *mut usize,
+) -> u8 {
+    dispatch(contract, calldata, calldata_len, return_data_len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn delegate_call_contract(
+    contract: *const u8,
+    calldata: *const u8,
+    calldata_len: usize,
+    _gas: u64,
+    return_data_len: *mut usize,
+) -> u8 {
+    dispatch(contract, calldata, calldata_len, return_data_len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn static_call_contract(
+    contract: *const u8,
+    calldata: *const u8,
+    calldata_len: usize,
+    _gas: u64,
+    return_data_len: *mut usize,
+) -> u8 {
+    dispatch(contract, calldata, calldata_len, return_data_len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn contract_address(address: *mut u8) {
+    write(address, CONTRACT.as_slice());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn create1(
+    _code: *const u8,
+    _code_len: usize,
+    _endowment: *const u8,
+    _contract: *mut u8,
+    _revert_data_len: *mut usize,
+) {
+    unimplemented!("deployments are not mocked")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn create2(
+    _code: *const u8,
+    _code_len: usize,
+    _endowment: *const u8,
+    _salt: *const u8,
+    _contract: *mut u8,
+    _revert_data_len: *mut usize,
+) {
+    unimplemented!("deployments are not mocked")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn emit_log(data: *const u8, len: usize, topics: usize) {
+    let data = slice::from_raw_parts(data, len);
+    let (topics, data) = data.split_at(topics * 32);
+    let topics = topics.chunks(32).map(B256::from_slice).collect();
+    HOST.with(|host| host.borrow_mut().logs.push((topics, data.to_vec())));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evm_gas_left() -> u64 {
+    GAS_LEFT
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn evm_ink_left() -> u64 {
+    GAS_LEFT
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pay_for_memory_grow(_pages: u16) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn msg_reentrant() -> bool {
+    false
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn msg_sender(sender: *mut u8) {
+    write(sender, CALLER.as_slice());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn msg_value(value: *mut u8) {
+    write(value, &[0; 32]);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+    let mut hasher = Keccak256::new();
+    hasher.update(slice::from_raw_parts(bytes, len));
+    write(output, hasher.finalize().as_slice());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn read_args(_dest: *mut u8) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn read_return_data(dest: *mut u8, offset: usize, size: usize) -> usize {
+    HOST.with(|host| {
+        let host = host.borrow();
+        let data = host.return_data.get(offset..).unwrap_or_default();
+        let data = &data[..size.min(data.len())];
+        write(dest, data);
+        data.len()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn write_result(_data: *const u8, _len: usize) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn return_data_size() -> usize {
+    HOST.with(|host| host.borrow().return_data.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tx_gas_price(gas_price: *mut u8) {
+    write(gas_price, &[0; 32]);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tx_ink_price() -> u32 {
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tx_origin(origin: *mut u8) {
+    write(origin, CALLER.as_slice());
+}
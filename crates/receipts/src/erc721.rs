@@ -0,0 +1,196 @@
+//! Implementation of the ERC-721 standard
+//!
+//! The eponymous [`Erc721`] type provides the standard methods and is
+//! intended to be inherited by other contract types. It is configured via
+//! the [`Erc721Params`] trait.
+//!
+//! Safe transfers are left to the inheriting contract: they call back into
+//! the recipient, which needs access to the top-level contract storage.
+//!
+//! Note that this code is unaudited and not fit for production use.
+
+// Imported packages
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::sol;
+use core::marker::PhantomData;
+use stylus_sdk::{evm, msg, prelude::*};
+
+pub trait Erc721Params {
+    /// Immutable collection name
+    const NAME: &'static str;
+
+    /// Immutable collection symbol
+    const SYMBOL: &'static str;
+}
+
+sol_storage! {
+    /// Erc721 implements all ERC-721 methods but safe transfers.
+    pub struct Erc721<T> {
+        /// Maps token ids to owners
+        mapping(uint256 => address) owners;
+        /// Maps users to the number of tokens they own
+        mapping(address => uint256) balances;
+        /// Maps token ids to the address approved to move them
+        mapping(uint256 => address) token_approvals;
+        /// Maps owners to a mapping of operators allowed to move all their tokens
+        mapping(address => mapping(address => bool)) operator_approvals;
+        /// Number of tokens ever minted, also the next token id
+        uint256 total_minted;
+        /// Used to allow [`Erc721Params`]
+        PhantomData<T> phantom;
+    }
+}
+
+// Declare events and Solidity error types
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 indexed token_id);
+    event Approval(address indexed owner, address indexed approved, uint256 indexed token_id);
+    event ApprovalForAll(address indexed owner, address indexed operator, bool approved);
+
+    error InvalidToken(uint256 token_id);
+    error NotOwner(address from, uint256 token_id, address real_owner);
+    error NotApproved(address owner, address spender, uint256 token_id);
+    error TransferToZero(uint256 token_id);
+}
+
+/// Represents the ways methods may fail.
+#[derive(SolidityError)]
+pub enum Erc721Error {
+    InvalidToken(InvalidToken),
+    NotOwner(NotOwner),
+    NotApproved(NotApproved),
+    TransferToZero(TransferToZero),
+}
+
+// These methods aren't exposed to other contracts
+// Methods marked as "pub" here are usable outside of the erc721 module (i.e. they're callable from lib.rs)
+impl<T: Erc721Params> Erc721<T> {
+    /// Whether `spender` may move `token_id` on behalf of its `owner`
+    pub fn _is_approved_or_owner(&self, owner: Address, spender: Address, token_id: U256) -> bool {
+        spender == owner
+            || self.token_approvals.get(token_id) == spender
+            || self.operator_approvals.getter(owner).get(spender)
+    }
+
+    /// Movement of `token_id` from `from` to `to`, without approval checks
+    pub fn _transfer(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.owner_of(token_id)?;
+        if owner != from {
+            return Err(Erc721Error::NotOwner(NotOwner { from, token_id, real_owner: owner }));
+        }
+        if to == Address::ZERO {
+            return Err(Erc721Error::TransferToZero(TransferToZero { token_id }));
+        }
+
+        // Clearing the approval of the previous owner
+        self.token_approvals.delete(token_id);
+
+        // Moving the token
+        let from_balance = self.balances.get(from);
+        self.balances.insert(from, from_balance - U256::from(1));
+        let to_balance = self.balances.get(to);
+        self.balances.insert(to, to_balance + U256::from(1));
+        self.owners.insert(token_id, to);
+
+        // Emitting the transfer event
+        evm::log(Transfer { from, to, token_id });
+        Ok(())
+    }
+
+    /// Mints the next token id to `to` and returns it
+    pub fn mint(&mut self, to: Address) -> Result<U256, Erc721Error> {
+        let token_id = self.total_minted.get();
+        if to == Address::ZERO {
+            return Err(Erc721Error::TransferToZero(TransferToZero { token_id }));
+        }
+        self.total_minted.set(token_id + U256::from(1));
+
+        let balance = self.balances.get(to);
+        self.balances.insert(to, balance + U256::from(1));
+        self.owners.insert(token_id, to);
+
+        // Emitting the transfer event
+        evm::log(Transfer { from: Address::ZERO, to, token_id });
+        Ok(token_id)
+    }
+
+    /// Burns `token_id`
+    pub fn burn(&mut self, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.owner_of(token_id)?;
+
+        self.token_approvals.delete(token_id);
+        let balance = self.balances.get(owner);
+        self.balances.insert(owner, balance - U256::from(1));
+        self.owners.delete(token_id);
+
+        // Emitting the transfer event
+        evm::log(Transfer { from: owner, to: Address::ZERO, token_id });
+        Ok(())
+    }
+}
+
+// These methods are public to other contracts
+#[public]
+impl<T: Erc721Params> Erc721<T> {
+    /// Immutable collection name
+    pub fn name() -> String {
+        T::NAME.into()
+    }
+
+    /// Immutable collection symbol
+    pub fn symbol() -> String {
+        T::SYMBOL.into()
+    }
+
+    /// Number of tokens owned by `owner`
+    pub fn balance_of(&self, owner: Address) -> U256 {
+        self.balances.get(owner)
+    }
+
+    /// Owner of `token_id`
+    pub fn owner_of(&self, token_id: U256) -> Result<Address, Erc721Error> {
+        let owner = self.owners.get(token_id);
+        if owner == Address::ZERO {
+            return Err(Erc721Error::InvalidToken(InvalidToken { token_id }));
+        }
+        Ok(owner)
+    }
+
+    /// Transfers `token_id` from `from` to `to`
+    /// (msg::sender() must own or be approved for the token)
+    pub fn transfer_from(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.owner_of(token_id)?;
+        if !self._is_approved_or_owner(owner, msg::sender(), token_id) {
+            return Err(Erc721Error::NotApproved(NotApproved { owner, spender: msg::sender(), token_id }));
+        }
+        self._transfer(from, to, token_id)
+    }
+
+    /// Approves `approved` to move `token_id` of msg::sender()
+    pub fn approve(&mut self, approved: Address, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.owner_of(token_id)?;
+        if msg::sender() != owner && !self.operator_approvals.getter(owner).get(msg::sender()) {
+            return Err(Erc721Error::NotApproved(NotApproved { owner, spender: msg::sender(), token_id }));
+        }
+        self.token_approvals.insert(token_id, approved);
+        evm::log(Approval { owner, approved, token_id });
+        Ok(())
+    }
+
+    /// Lets `operator` move every token of msg::sender()
+    pub fn set_approval_for_all(&mut self, operator: Address, approved: bool) {
+        self.operator_approvals.setter(msg::sender()).insert(operator, approved);
+        evm::log(ApprovalForAll { owner: msg::sender(), operator, approved });
+    }
+
+    /// Address approved to move `token_id`
+    pub fn get_approved(&self, token_id: U256) -> Result<Address, Erc721Error> {
+        self.owner_of(token_id)?;
+        Ok(self.token_approvals.get(token_id))
+    }
+
+    /// Whether `operator` may move every token of `owner`
+    pub fn is_approved_for_all(&self, owner: Address, operator: Address) -> bool {
+        self.operator_approvals.getter(owner).get(operator)
+    }
+}
@@ -0,0 +1,248 @@
+//! Deposit receipts of the Metric vault
+//!
+//! In receipt accounting mode the vault mints one of these ERC-721 receipts
+//! per deposit, recording the lot's shares, cost and acquisition time. The
+//! receipts live in their own contract so that wallets and indexers see a
+//! standard ERC-721 apart from the vault's ERC-20 shares.
+//!
+//! Only the vault mints and burns receipts. Every transfer is reported back
+//! to the vault, which moves the shares the receipt records along with it,
+//! so a receipt and its shares always change hands together.
+
+// Only run this as a WASM if the export-abi feature is not set.
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+extern crate alloc;
+
+// Modules and imports
+mod erc721;
+
+use crate::erc721::{Erc721, Erc721Error, Erc721Params, InvalidToken, NotApproved, NotOwner, TransferToZero};
+use alloy_primitives::{Address, FixedBytes, U256};
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::{abi::Bytes, call::{call, Call}, msg, prelude::*};
+use vault_abi::receipts::{
+    IERC721Receiver, IReceiptVault, ERC165_INTERFACE_ID, ERC721_INTERFACE_ID, ERC721_METADATA_INTERFACE_ID,
+};
+
+/// Immutable definitions
+struct ReceiptParams;
+impl Erc721Params for ReceiptParams {
+    const NAME: &'static str = "Metric Lot Receipt";
+    const SYMBOL: &'static str = "METRIC-LOT";
+}
+
+sol_storage! {
+    #[entrypoint]
+    struct Receipts {
+        #[borrow]
+        Erc721<ReceiptParams> erc721;
+        address vault;
+    }
+}
+
+// Declare Solidity error types
+sol! {
+    error NotAuthorized();
+    error UnsafeRecipient(address to);
+}
+
+/// Represents the ways receipt methods may fail.
+#[derive(SolidityError)]
+pub enum ReceiptsError {
+    NotAuthorized(NotAuthorized),
+    UnsafeRecipient(UnsafeRecipient),
+    InvalidToken(InvalidToken),
+    NotOwner(NotOwner),
+    NotApproved(NotApproved),
+    TransferToZero(TransferToZero),
+}
+
+impl From<Erc721Error> for ReceiptsError {
+    fn from(err: Erc721Error) -> Self {
+        match err {
+            Erc721Error::InvalidToken(e) => ReceiptsError::InvalidToken(e),
+            Erc721Error::NotOwner(e) => ReceiptsError::NotOwner(e),
+            Erc721Error::NotApproved(e) => ReceiptsError::NotApproved(e),
+            Erc721Error::TransferToZero(e) => ReceiptsError::TransferToZero(e),
+        }
+    }
+}
+
+#[public]
+#[inherit(Erc721<ReceiptParams>)]
+impl Receipts {
+    /// Binds the receipts to the calling vault; only possible once. The
+    /// vault calls this itself when the admin sets it as its receipts
+    /// contract, so a receipts contract someone else initialized first is
+    /// rejected there rather than handed to the wrong vault.
+    pub fn initialize(&mut self) -> Result<(), ReceiptsError> {
+        if self.vault.get() != Address::ZERO {
+            return Err(ReceiptsError::NotAuthorized(NotAuthorized {}));
+        }
+        self.vault.set(msg::sender());
+        Ok(())
+    }
+
+    /// Vault the receipts belong to
+    pub fn vault(&self) -> Address {
+        self.vault.get()
+    }
+
+    /// Mints the next receipt to `to` and returns its id (vault only)
+    pub fn mint(&mut self, to: Address) -> Result<U256, ReceiptsError> {
+        self._only_vault()?;
+        Ok(self.erc721.mint(to)?)
+    }
+
+    /// Burns `token_id` (vault only)
+    pub fn burn(&mut self, token_id: U256) -> Result<(), ReceiptsError> {
+        self._only_vault()?;
+        Ok(self.erc721.burn(token_id)?)
+    }
+
+    /// Transfers `token_id` from `from` to `to`, together with the shares
+    /// it records (msg::sender() must own or be approved for the token)
+    pub fn transfer_from(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Vec<u8>> {
+        self.erc721.transfer_from(from, to, token_id).map_err(ReceiptsError::from)?;
+
+        // Have the vault move the receipt's shares, reverting with it
+        let vault = self.vault.get();
+        let moved = IReceiptVault::onReceiptTransferCall { from, to, receiptId: token_id };
+        call(Call::new_in(self), vault, &moved.abi_encode())?;
+        Ok(())
+    }
+
+    /// Transfers `token_id` like `transferFrom`, checking that a contract
+    /// recipient accepts ERC-721 tokens
+    #[selector(name = "safeTransferFrom")]
+    pub fn safe_transfer_from(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Vec<u8>> {
+        self.safe_transfer_from_with_data(from, to, token_id, Bytes(Vec::new()))
+    }
+
+    /// Transfers `token_id` like `transferFrom`, passing `data` to a
+    /// contract recipient, which must accept ERC-721 tokens
+    #[selector(name = "safeTransferFrom")]
+    pub fn safe_transfer_from_with_data(&mut self, from: Address, to: Address, token_id: U256, data: Bytes) -> Result<(), Vec<u8>> {
+        self.transfer_from(from, to, token_id)?;
+        if !to.has_code() {
+            return Ok(());
+        }
+
+        let received = IERC721Receiver::onERC721ReceivedCall { operator: msg::sender(), from, tokenId: token_id, data: data.0.into() };
+        let accepted = call(Call::new_in(self), to, &received.abi_encode())
+            .ok()
+            .and_then(|ret| IERC721Receiver::onERC721ReceivedCall::abi_decode_returns(&ret, true).ok())
+            .is_some_and(|ret| ret._0 == IERC721Receiver::onERC721ReceivedCall::SELECTOR);
+        if !accepted {
+            return Err(ReceiptsError::UnsafeRecipient(UnsafeRecipient { to }).into());
+        }
+        Ok(())
+    }
+
+    /// Whether the contract implements `interface_id`: ERC-165, ERC-721 and
+    /// its metadata extension
+    pub fn supports_interface(interface_id: FixedBytes<4>) -> bool {
+        [ERC165_INTERFACE_ID, ERC721_INTERFACE_ID, ERC721_METADATA_INTERFACE_ID].contains(&interface_id)
+    }
+}
+
+// internal functions
+impl Receipts {
+    /// Reverts unless the caller is the vault
+    pub fn _only_vault(&self) -> Result<(), ReceiptsError> {
+        if msg::sender() != self.vault.get() {
+            return Err(ReceiptsError::NotAuthorized(NotAuthorized {}));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use alloy_sol_types::SolError;
+    use mock_host::{calls_to, mock_contract, reset, CALLER};
+
+    const BOB: Address = address!("b0b0000000000000000000000000000000000002");
+
+    /// Fresh receipts bound to `CALLER`, which stands in for the vault
+    fn receipts() -> Receipts {
+        reset();
+        let mut receipts = unsafe { Receipts::new(U256::ZERO, 0) };
+        receipts.initialize().ok().unwrap();
+        receipts
+    }
+
+    /// Has the vault accept every receipt transfer
+    fn accept_transfers() {
+        mock_contract(CALLER, |_| Ok(Vec::new()));
+    }
+
+    #[test]
+    fn binds_to_the_first_caller_once() {
+        let mut receipts = receipts();
+        assert_eq!(receipts.vault(), CALLER);
+        assert!(matches!(receipts.initialize(), Err(ReceiptsError::NotAuthorized(_))));
+    }
+
+    #[test]
+    fn mints_for_the_vault() {
+        let mut receipts = receipts();
+        let first = receipts.mint(BOB).ok().unwrap();
+        let second = receipts.mint(BOB).ok().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(receipts.erc721.owner_of(first).ok(), Some(BOB));
+        assert_eq!(receipts.erc721.balance_of(BOB), U256::from(2));
+    }
+
+    #[test]
+    fn only_the_vault_mints_and_burns() {
+        let mut receipts = receipts();
+        let id = receipts.mint(BOB).ok().unwrap();
+        receipts.vault.set(BOB);
+        assert!(matches!(receipts.mint(CALLER), Err(ReceiptsError::NotAuthorized(_))));
+        assert!(matches!(receipts.burn(id), Err(ReceiptsError::NotAuthorized(_))));
+    }
+
+    #[test]
+    fn reports_transfers_to_the_vault() {
+        let mut receipts = receipts();
+        accept_transfers();
+        let id = receipts.mint(CALLER).ok().unwrap();
+        receipts.transfer_from(CALLER, BOB, id).unwrap();
+
+        assert_eq!(receipts.erc721.owner_of(id).ok(), Some(BOB));
+        let moved = IReceiptVault::onReceiptTransferCall { from: CALLER, to: BOB, receiptId: id };
+        assert_eq!(calls_to(CALLER), vec![moved.abi_encode()]);
+    }
+
+    #[test]
+    fn reverts_with_the_vault() {
+        let mut receipts = receipts();
+        mock_contract(CALLER, |_| Err(NotAuthorized {}.abi_encode()));
+        let id = receipts.mint(CALLER).ok().unwrap();
+        assert_eq!(receipts.transfer_from(CALLER, BOB, id), Err(NotAuthorized {}.abi_encode()));
+    }
+
+    #[test]
+    fn rejects_contracts_that_do_not_accept_receipts() {
+        let mut receipts = receipts();
+        accept_transfers();
+        mock_contract(BOB, |_| Ok(FixedBytes::<32>::ZERO.to_vec()));
+        let id = receipts.mint(CALLER).ok().unwrap();
+        assert_eq!(
+            receipts.safe_transfer_from(CALLER, BOB, id),
+            Err(UnsafeRecipient { to: BOB }.abi_encode())
+        );
+    }
+
+    #[test]
+    fn burns_for_the_vault() {
+        let mut receipts = receipts();
+        let id = receipts.mint(BOB).ok().unwrap();
+        receipts.burn(id).ok().unwrap();
+        assert!(matches!(receipts.erc721.owner_of(id), Err(Erc721Error::InvalidToken(_))));
+        assert_eq!(receipts.erc721.balance_of(BOB), U256::ZERO);
+    }
+}
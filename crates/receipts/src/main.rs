@@ -0,0 +1,7 @@
+#[cfg(feature = "export-abi")]
+fn main() {
+    metric_receipts::print_abi("MIT-OR-APACHE-2.0", "pragma solidity ^0.8.28;");
+}
+
+#[cfg(not(feature = "export-abi"))]
+fn main() {}
@@ -0,0 +1,616 @@
+//! Swap venues the vault can trade on
+//!
+//! Every venue is reached through a [`DexAdapter`], which turns a swap into
+//! calldata for the venue's router and reads the amount received or spent
+//! back out of the router's return data. [`Venue`] names the venues a token
+//! pair can be routed through.
+
+// Imported packages
+use crate::router::{decode_amount_in, decode_amount_out, decode_path, ExactInput, ExactInputSingle, ExactOutput, ExactOutputSingle, RouterKind};
+use alloc::{boxed::Box, vec, vec::Vec};
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall};
+
+sol! {
+    /// Uniswap V2 style router, shared by most V2 forks
+    interface IUniswapV2Router {
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] calldata path, address to, uint256 deadline) external returns (uint256[] memory amounts);
+        function swapTokensForExactTokens(uint256 amountOut, uint256 amountInMax, address[] calldata path, address to, uint256 deadline) external returns (uint256[] memory amounts);
+    }
+}
+
+sol! {
+    /// Algebra swap router, used by Camelot V3 on Arbitrum
+    interface IAlgebraSwapRouter {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            address recipient;
+            uint256 deadline;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 limitSqrtPrice;
+        }
+
+        struct ExactInputParams {
+            bytes path;
+            address recipient;
+            uint256 deadline;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+        }
+
+        // Unlike the other params, these keep Uniswap's unused fee
+        struct ExactOutputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 deadline;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+            uint160 limitSqrtPrice;
+        }
+
+        struct ExactOutputParams {
+            bytes path;
+            address recipient;
+            uint256 deadline;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut);
+        function exactInput(ExactInputParams calldata params) external payable returns (uint256 amountOut);
+        function exactOutputSingle(ExactOutputSingleParams calldata params) external payable returns (uint256 amountIn);
+        function exactOutput(ExactOutputParams calldata params) external payable returns (uint256 amountIn);
+    }
+}
+
+/// Encodes swaps for one venue's router and decodes what it returns
+pub trait DexAdapter {
+    /// Calldata swapping exactly `swap.amount_in` of `swap.token_in` for
+    /// `swap.token_out`. Fields the venue has no use for are ignored.
+    fn encode_exact_input_single(&self, swap: &ExactInputSingle, deadline: U256) -> Vec<u8>;
+
+    /// Calldata swapping exactly `swap.amount_in` along `swap.path`, a
+    /// Uniswap V3 path from the input token to the output token. Venues
+    /// without fee tiers route through the path's tokens alone.
+    fn encode_exact_input(&self, swap: &ExactInput, deadline: U256) -> Vec<u8>;
+
+    /// Amount of `token_out` received, read from the router's return data
+    fn decode_amount_out(&self, return_data: &[u8]) -> Option<U256>;
+
+    /// Calldata swapping at most `swap.amount_in_maximum` of `swap.token_in`
+    /// for exactly `swap.amount_out` of `swap.token_out`
+    fn encode_exact_output_single(&self, swap: &ExactOutputSingle, deadline: U256) -> Vec<u8>;
+
+    /// Calldata for an exact output swap along `swap.path`, a Uniswap V3
+    /// path running from the output token back to the input token
+    fn encode_exact_output(&self, swap: &ExactOutput, deadline: U256) -> Vec<u8>;
+
+    /// Amount of `token_in` spent by an exact output swap, read from the
+    /// router's return data
+    fn decode_amount_in(&self, return_data: &[u8]) -> Option<U256>;
+}
+
+/// Tokens of a Uniswap V3 `path`, in the order they appear; empty if the
+/// path is malformed, which the router rejects
+fn path_tokens(path: &[u8]) -> Vec<Address> {
+    decode_path(path).map(|(tokens, _)| tokens).unwrap_or_default()
+}
+
+/// Algebra path of a Uniswap V3 `path`: its tokens packed without fee tiers
+fn algebra_path(path: &[u8]) -> Vec<u8> {
+    path_tokens(path).iter().flat_map(|token| token.into_array()).collect()
+}
+
+/// Uniswap V3 through either of its routers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniswapV3 {
+    pub kind: RouterKind,
+}
+
+impl DexAdapter for UniswapV3 {
+    fn encode_exact_input_single(&self, swap: &ExactInputSingle, deadline: U256) -> Vec<u8> {
+        self.kind.encode_exact_input_single(swap, deadline)
+    }
+
+    fn encode_exact_input(&self, swap: &ExactInput, deadline: U256) -> Vec<u8> {
+        self.kind.encode_exact_input(swap, deadline)
+    }
+
+    fn decode_amount_out(&self, return_data: &[u8]) -> Option<U256> {
+        decode_amount_out(return_data)
+    }
+
+    fn encode_exact_output_single(&self, swap: &ExactOutputSingle, deadline: U256) -> Vec<u8> {
+        self.kind.encode_exact_output_single(swap, deadline)
+    }
+
+    fn encode_exact_output(&self, swap: &ExactOutput, deadline: U256) -> Vec<u8> {
+        self.kind.encode_exact_output(swap, deadline)
+    }
+
+    fn decode_amount_in(&self, return_data: &[u8]) -> Option<U256> {
+        decode_amount_in(return_data)
+    }
+}
+
+/// Uniswap V2 style routers; pools have a single fixed fee, so the fee tier
+/// and price limit of the swap are ignored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniswapV2;
+
+impl DexAdapter for UniswapV2 {
+    fn encode_exact_input_single(&self, swap: &ExactInputSingle, deadline: U256) -> Vec<u8> {
+        IUniswapV2Router::swapExactTokensForTokensCall {
+            amountIn: swap.amount_in,
+            amountOutMin: swap.amount_out_minimum,
+            path: vec![swap.token_in, swap.token_out],
+            to: swap.recipient,
+            deadline,
+        }
+        .abi_encode()
+    }
+
+    fn encode_exact_input(&self, swap: &ExactInput, deadline: U256) -> Vec<u8> {
+        IUniswapV2Router::swapExactTokensForTokensCall {
+            amountIn: swap.amount_in,
+            amountOutMin: swap.amount_out_minimum,
+            path: path_tokens(&swap.path),
+            to: swap.recipient,
+            deadline,
+        }
+        .abi_encode()
+    }
+
+    fn decode_amount_out(&self, return_data: &[u8]) -> Option<U256> {
+        // The router returns the amount after every hop, the last one is ours
+        IUniswapV2Router::swapExactTokensForTokensCall::abi_decode_returns(return_data, true)
+            .ok()
+            .and_then(|ret| ret.amounts.last().copied())
+    }
+
+    fn encode_exact_output_single(&self, swap: &ExactOutputSingle, deadline: U256) -> Vec<u8> {
+        IUniswapV2Router::swapTokensForExactTokensCall {
+            amountOut: swap.amount_out,
+            amountInMax: swap.amount_in_maximum,
+            path: vec![swap.token_in, swap.token_out],
+            to: swap.recipient,
+            deadline,
+        }
+        .abi_encode()
+    }
+
+    fn encode_exact_output(&self, swap: &ExactOutput, deadline: U256) -> Vec<u8> {
+        // V2 paths run from input to output
+        let mut path = path_tokens(&swap.path);
+        path.reverse();
+        IUniswapV2Router::swapTokensForExactTokensCall {
+            amountOut: swap.amount_out,
+            amountInMax: swap.amount_in_maximum,
+            path,
+            to: swap.recipient,
+            deadline,
+        }
+        .abi_encode()
+    }
+
+    fn decode_amount_in(&self, return_data: &[u8]) -> Option<U256> {
+        // The first amount is what the router pulled from us
+        IUniswapV2Router::swapTokensForExactTokensCall::abi_decode_returns(return_data, true)
+            .ok()
+            .and_then(|ret| ret.amounts.first().copied())
+    }
+}
+
+/// Algebra routers such as Camelot V3; pools set their own dynamic fee, so
+/// the fee tier of the swap is ignored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Algebra;
+
+impl DexAdapter for Algebra {
+    fn encode_exact_input_single(&self, swap: &ExactInputSingle, deadline: U256) -> Vec<u8> {
+        IAlgebraSwapRouter::exactInputSingleCall {
+            params: IAlgebraSwapRouter::ExactInputSingleParams {
+                tokenIn: swap.token_in,
+                tokenOut: swap.token_out,
+                recipient: swap.recipient,
+                deadline,
+                amountIn: swap.amount_in,
+                amountOutMinimum: swap.amount_out_minimum,
+                limitSqrtPrice: swap.sqrt_price_limit_x96,
+            },
+        }
+        .abi_encode()
+    }
+
+    fn encode_exact_input(&self, swap: &ExactInput, deadline: U256) -> Vec<u8> {
+        IAlgebraSwapRouter::exactInputCall {
+            params: IAlgebraSwapRouter::ExactInputParams {
+                path: algebra_path(&swap.path).into(),
+                recipient: swap.recipient,
+                deadline,
+                amountIn: swap.amount_in,
+                amountOutMinimum: swap.amount_out_minimum,
+            },
+        }
+        .abi_encode()
+    }
+
+    fn decode_amount_out(&self, return_data: &[u8]) -> Option<U256> {
+        IAlgebraSwapRouter::exactInputSingleCall::abi_decode_returns(return_data, true)
+            .ok()
+            .map(|ret| ret.amountOut)
+    }
+
+    fn encode_exact_output_single(&self, swap: &ExactOutputSingle, deadline: U256) -> Vec<u8> {
+        IAlgebraSwapRouter::exactOutputSingleCall {
+            params: IAlgebraSwapRouter::ExactOutputSingleParams {
+                tokenIn: swap.token_in,
+                tokenOut: swap.token_out,
+                fee: 0,
+                recipient: swap.recipient,
+                deadline,
+                amountOut: swap.amount_out,
+                amountInMaximum: swap.amount_in_maximum,
+                limitSqrtPrice: swap.sqrt_price_limit_x96,
+            },
+        }
+        .abi_encode()
+    }
+
+    fn encode_exact_output(&self, swap: &ExactOutput, deadline: U256) -> Vec<u8> {
+        IAlgebraSwapRouter::exactOutputCall {
+            params: IAlgebraSwapRouter::ExactOutputParams {
+                path: algebra_path(&swap.path).into(),
+                recipient: swap.recipient,
+                deadline,
+                amountOut: swap.amount_out,
+                amountInMaximum: swap.amount_in_maximum,
+            },
+        }
+        .abi_encode()
+    }
+
+    fn decode_amount_in(&self, return_data: &[u8]) -> Option<U256> {
+        IAlgebraSwapRouter::exactOutputSingleCall::abi_decode_returns(return_data, true)
+            .ok()
+            .map(|ret| ret.amountIn)
+    }
+}
+
+/// Venue a token pair is routed through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Venue {
+    /// Uniswap V3, the default for pairs without a venue of their own
+    UniswapV3 = 0,
+    UniswapV2 = 1,
+    /// Camelot V3 and other Algebra deployments
+    Algebra = 2,
+}
+
+impl Venue {
+    pub fn from_u8(venue: u8) -> Option<Self> {
+        match venue {
+            0 => Some(Self::UniswapV3),
+            1 => Some(Self::UniswapV2),
+            2 => Some(Self::Algebra),
+            _ => None,
+        }
+    }
+
+    /// Adapter for the venue; `kind` selects the Uniswap V3 router flavour
+    pub fn adapter(self, kind: RouterKind) -> Box<dyn DexAdapter> {
+        match self {
+            Self::UniswapV3 => Box::new(UniswapV3 { kind }),
+            Self::UniswapV2 => Box::new(UniswapV2),
+            Self::Algebra => Box::new(Algebra),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::{encode_path, ISwapRouter, ISwapRouter02};
+    use alloy_primitives::{address, hex};
+
+    const DEADLINE: U256 = U256::from_limbs([1_700_000_000, 0, 0, 0]);
+
+    fn swap() -> ExactInputSingle {
+        ExactInputSingle {
+            token_in: address!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d"),
+            token_out: address!("980b62da83eff3d4576c647993b0c1d7faf17c73"),
+            fee: 3000,
+            recipient: address!("bebbe2bacc1f5caf9a471838b7567ff636093c84"),
+            amount_in: U256::from(100),
+            amount_out_minimum: U256::from(90),
+            sqrt_price_limit_x96: U256::ZERO,
+        }
+    }
+
+    /// What a router decoded out of the calldata it was sent
+    #[derive(Debug, PartialEq, Eq)]
+    struct Order {
+        token_in: Address,
+        token_out: Address,
+        recipient: Address,
+        amount_in: U256,
+        amount_out_minimum: U256,
+    }
+
+    /// Stands in for a venue's router: decodes the call with the router's
+    /// own ABI, fills it at a rate of 2 and returns what the router would
+    fn mock_router(venue: Venue, calldata: &[u8]) -> Option<(Order, Vec<u8>)> {
+        let (order, amount_out) = match venue {
+            Venue::UniswapV3 => {
+                let params = ISwapRouter02::exactInputSingleCall::abi_decode(calldata, true).ok()?.params;
+                let order = Order {
+                    token_in: params.tokenIn,
+                    token_out: params.tokenOut,
+                    recipient: params.recipient,
+                    amount_in: params.amountIn,
+                    amount_out_minimum: params.amountOutMinimum,
+                };
+                let amount_out = order.amount_in * U256::from(2);
+                (order, ISwapRouter02::exactInputSingleCall::abi_encode_returns(&(amount_out,)))
+            }
+            Venue::UniswapV2 => {
+                let call = IUniswapV2Router::swapExactTokensForTokensCall::abi_decode(calldata, true).ok()?;
+                let order = Order {
+                    token_in: *call.path.first()?,
+                    token_out: *call.path.last()?,
+                    recipient: call.to,
+                    amount_in: call.amountIn,
+                    amount_out_minimum: call.amountOutMin,
+                };
+                let amounts = vec![order.amount_in, order.amount_in * U256::from(2)];
+                (order, IUniswapV2Router::swapExactTokensForTokensCall::abi_encode_returns(&(amounts,)))
+            }
+            Venue::Algebra => {
+                let params = IAlgebraSwapRouter::exactInputSingleCall::abi_decode(calldata, true).ok()?.params;
+                let order = Order {
+                    token_in: params.tokenIn,
+                    token_out: params.tokenOut,
+                    recipient: params.recipient,
+                    amount_in: params.amountIn,
+                    amount_out_minimum: params.amountOutMinimum,
+                };
+                let amount_out = order.amount_in * U256::from(2);
+                (order, IAlgebraSwapRouter::exactInputSingleCall::abi_encode_returns(&(amount_out,)))
+            }
+        };
+        Some((order, amount_out))
+    }
+
+    /// What a router decoded out of the exact output calldata it was sent;
+    /// `tokens` run from input to output
+    #[derive(Debug, PartialEq, Eq)]
+    struct OutputOrder {
+        tokens: Vec<Address>,
+        recipient: Address,
+        amount_out: U256,
+        amount_in_maximum: U256,
+    }
+
+    /// Stands in for a venue's router on exact output swaps: decodes the
+    /// call with the router's own ABI, fills it at a rate of 2 and returns
+    /// what the router would
+    fn mock_exact_output_router(venue: Venue, calldata: &[u8]) -> Option<(OutputOrder, Vec<u8>)> {
+        let order = match venue {
+            Venue::UniswapV3 => {
+                if let Ok(call) = ISwapRouter02::exactOutputSingleCall::abi_decode(calldata, true) {
+                    let params = call.params;
+                    OutputOrder { tokens: vec![params.tokenIn, params.tokenOut], recipient: params.recipient, amount_out: params.amountOut, amount_in_maximum: params.amountInMaximum }
+                } else {
+                    let params = ISwapRouter02::exactOutputCall::abi_decode(calldata, true).ok()?.params;
+                    let mut tokens = decode_path(&params.path)?.0;
+                    tokens.reverse();
+                    OutputOrder { tokens, recipient: params.recipient, amount_out: params.amountOut, amount_in_maximum: params.amountInMaximum }
+                }
+            }
+            Venue::UniswapV2 => {
+                let call = IUniswapV2Router::swapTokensForExactTokensCall::abi_decode(calldata, true).ok()?;
+                OutputOrder { tokens: call.path, recipient: call.to, amount_out: call.amountOut, amount_in_maximum: call.amountInMax }
+            }
+            Venue::Algebra => {
+                if let Ok(call) = IAlgebraSwapRouter::exactOutputSingleCall::abi_decode(calldata, true) {
+                    let params = call.params;
+                    OutputOrder { tokens: vec![params.tokenIn, params.tokenOut], recipient: params.recipient, amount_out: params.amountOut, amount_in_maximum: params.amountInMaximum }
+                } else {
+                    let params = IAlgebraSwapRouter::exactOutputCall::abi_decode(calldata, true).ok()?.params;
+                    if params.path.is_empty() || params.path.len() % 20 != 0 {
+                        return None;
+                    }
+                    let mut tokens = params.path.chunks(20).map(Address::from_slice).collect::<Vec<_>>();
+                    tokens.reverse();
+                    OutputOrder { tokens, recipient: params.recipient, amount_out: params.amountOut, amount_in_maximum: params.amountInMaximum }
+                }
+            }
+        };
+
+        let amount_in = order.amount_out / U256::from(2);
+        let returned = match venue {
+            Venue::UniswapV3 => ISwapRouter02::exactOutputCall::abi_encode_returns(&(amount_in,)),
+            Venue::UniswapV2 => IUniswapV2Router::swapTokensForExactTokensCall::abi_encode_returns(&(vec![amount_in, order.amount_out],)),
+            Venue::Algebra => IAlgebraSwapRouter::exactOutputCall::abi_encode_returns(&(amount_in,)),
+        };
+        Some((order, returned))
+    }
+
+    #[test]
+    fn swaps_through_every_venue() {
+        let swap = swap();
+        let expected = Order {
+            token_in: swap.token_in,
+            token_out: swap.token_out,
+            recipient: swap.recipient,
+            amount_in: swap.amount_in,
+            amount_out_minimum: swap.amount_out_minimum,
+        };
+
+        for venue in [Venue::UniswapV3, Venue::UniswapV2, Venue::Algebra] {
+            let adapter = venue.adapter(RouterKind::SwapRouter02);
+            let calldata = adapter.encode_exact_input_single(&swap, DEADLINE);
+            let (order, returned) = mock_router(venue, &calldata).unwrap();
+            assert_eq!(order, expected, "{:?}", venue);
+            assert_eq!(adapter.decode_amount_out(&returned), Some(U256::from(200)), "{:?}", venue);
+        }
+    }
+
+    /// Stands in for a venue's router on multi-hop swaps: decodes the call
+    /// with the router's own ABI and returns the tokens it would route
+    /// through and the amount it would send, filled at a rate of 2
+    fn mock_path_router(venue: Venue, calldata: &[u8]) -> Option<(Vec<Address>, Vec<u8>)> {
+        match venue {
+            Venue::UniswapV3 => {
+                let params = ISwapRouter02::exactInputCall::abi_decode(calldata, true).ok()?.params;
+                let amount_out = params.amountIn * U256::from(2);
+                Some((decode_path(&params.path)?.0, ISwapRouter02::exactInputCall::abi_encode_returns(&(amount_out,))))
+            }
+            Venue::UniswapV2 => {
+                let call = IUniswapV2Router::swapExactTokensForTokensCall::abi_decode(calldata, true).ok()?;
+                let amounts = vec![call.amountIn, call.amountIn, call.amountIn * U256::from(2)];
+                Some((call.path, IUniswapV2Router::swapExactTokensForTokensCall::abi_encode_returns(&(amounts,))))
+            }
+            Venue::Algebra => {
+                let params = IAlgebraSwapRouter::exactInputCall::abi_decode(calldata, true).ok()?.params;
+                if params.path.is_empty() || params.path.len() % 20 != 0 {
+                    return None;
+                }
+                let amount_out = params.amountIn * U256::from(2);
+                let tokens = params.path.chunks(20).map(Address::from_slice).collect();
+                Some((tokens, IAlgebraSwapRouter::exactInputCall::abi_encode_returns(&(amount_out,))))
+            }
+        }
+    }
+
+    #[test]
+    fn swaps_along_paths_through_every_venue() {
+        let swap = swap();
+        let usdc = address!("af88d065e77c8cc2239327c5edb3a432268e5831");
+        let path = ExactInput {
+            path: encode_path(&[swap.token_in, usdc, swap.token_out], &[3000, 500]).unwrap(),
+            recipient: swap.recipient,
+            amount_in: U256::from(100),
+            amount_out_minimum: U256::from(190),
+        };
+
+        for venue in [Venue::UniswapV3, Venue::UniswapV2, Venue::Algebra] {
+            let adapter = venue.adapter(RouterKind::SwapRouter02);
+            let (tokens, returned) = mock_path_router(venue, &adapter.encode_exact_input(&path, DEADLINE)).unwrap();
+            assert_eq!(tokens, vec![swap.token_in, usdc, swap.token_out], "{:?}", venue);
+            assert_eq!(adapter.decode_amount_out(&returned), Some(U256::from(200)), "{:?}", venue);
+        }
+
+        // Algebra pools have no fee tiers, so its path is tokens alone
+        let call = IAlgebraSwapRouter::exactInputCall::abi_decode(&Algebra.encode_exact_input(&path, DEADLINE), true).unwrap();
+        assert_eq!(call.params.path.len(), 60);
+        assert_eq!(call.params.deadline, DEADLINE);
+    }
+
+    #[test]
+    fn swaps_for_exact_outputs_through_every_venue() {
+        let swap = swap();
+        let single = ExactOutputSingle {
+            token_in: swap.token_in,
+            token_out: swap.token_out,
+            fee: swap.fee,
+            recipient: swap.recipient,
+            amount_out: U256::from(200),
+            amount_in_maximum: U256::from(110),
+            sqrt_price_limit_x96: U256::ZERO,
+        };
+        let usdc = address!("af88d065e77c8cc2239327c5edb3a432268e5831");
+        let path = ExactOutput {
+            // Output first: token_out <- usdc <- token_in
+            path: encode_path(&[swap.token_out, usdc, swap.token_in], &[500, 3000]).unwrap(),
+            recipient: swap.recipient,
+            amount_out: U256::from(200),
+            amount_in_maximum: U256::from(110),
+        };
+
+        for venue in [Venue::UniswapV3, Venue::UniswapV2, Venue::Algebra] {
+            let adapter = venue.adapter(RouterKind::SwapRouter02);
+
+            let (order, returned) = mock_exact_output_router(venue, &adapter.encode_exact_output_single(&single, DEADLINE)).unwrap();
+            let expected = OutputOrder { tokens: vec![swap.token_in, swap.token_out], recipient: swap.recipient, amount_out: U256::from(200), amount_in_maximum: U256::from(110) };
+            assert_eq!(order, expected, "{:?}", venue);
+            assert_eq!(adapter.decode_amount_in(&returned), Some(U256::from(100)), "{:?}", venue);
+
+            let (order, returned) = mock_exact_output_router(venue, &adapter.encode_exact_output(&path, DEADLINE)).unwrap();
+            assert_eq!(order.tokens, vec![swap.token_in, usdc, swap.token_out], "{:?}", venue);
+            assert_eq!(order.amount_in_maximum, U256::from(110), "{:?}", venue);
+            assert_eq!(adapter.decode_amount_in(&returned), Some(U256::from(100)), "{:?}", venue);
+        }
+    }
+
+    #[test]
+    fn uses_each_venues_exact_output_selectors() {
+        let swap = swap();
+        let single = ExactOutputSingle {
+            token_in: swap.token_in,
+            token_out: swap.token_out,
+            fee: swap.fee,
+            recipient: swap.recipient,
+            amount_out: U256::from(200),
+            amount_in_maximum: U256::from(110),
+            sqrt_price_limit_x96: U256::ZERO,
+        };
+        let selector = |venue: Venue| hex::encode(&venue.adapter(RouterKind::SwapRouter02).encode_exact_output_single(&single, DEADLINE)[..4]);
+        assert_eq!(selector(Venue::UniswapV3), "5023b4df");
+        assert_eq!(selector(Venue::UniswapV2), "8803dbee");
+        // Same params as the original SwapRouter, so the same selector
+        assert_eq!(selector(Venue::Algebra), "db3e2198");
+        assert_eq!(UniswapV2.decode_amount_in(&[]), None);
+    }
+
+    #[test]
+    fn routers_reject_other_venues_calls() {
+        let venues = [Venue::UniswapV3, Venue::UniswapV2, Venue::Algebra];
+        for venue in venues {
+            let calldata = venue.adapter(RouterKind::SwapRouter02).encode_exact_input_single(&swap(), DEADLINE);
+            for router in venues.into_iter().filter(|router| *router != venue) {
+                assert!(mock_router(router, &calldata).is_none(), "{:?} accepted {:?}", router, venue);
+            }
+        }
+    }
+
+    #[test]
+    fn uses_each_venues_selector() {
+        let selector = |venue: Venue, kind: RouterKind| hex::encode(&venue.adapter(kind).encode_exact_input_single(&swap(), DEADLINE)[..4]);
+        assert_eq!(selector(Venue::UniswapV3, RouterKind::SwapRouter02), "04e45aaf");
+        assert_eq!(selector(Venue::UniswapV3, RouterKind::SwapRouter), "414bf389");
+        assert_eq!(selector(Venue::UniswapV2, RouterKind::SwapRouter02), "38ed1739");
+        assert_eq!(selector(Venue::Algebra, RouterKind::SwapRouter02), "bc651188");
+    }
+
+    #[test]
+    fn passes_deadlines_and_price_limits_through() {
+        let mut swap = swap();
+        swap.sqrt_price_limit_x96 = U256::from(7);
+
+        let call = ISwapRouter::exactInputSingleCall::abi_decode(
+            &Venue::UniswapV3.adapter(RouterKind::SwapRouter).encode_exact_input_single(&swap, DEADLINE),
+            true,
+        )
+        .unwrap();
+        assert_eq!(call.params.deadline, DEADLINE);
+
+        let call = IUniswapV2Router::swapExactTokensForTokensCall::abi_decode(&UniswapV2.encode_exact_input_single(&swap, DEADLINE), true).unwrap();
+        assert_eq!(call.deadline, DEADLINE);
+
+        let call = IAlgebraSwapRouter::exactInputSingleCall::abi_decode(&Algebra.encode_exact_input_single(&swap, DEADLINE), true).unwrap();
+        assert_eq!(call.params.deadline, DEADLINE);
+        assert_eq!(call.params.limitSqrtPrice, swap.sqrt_price_limit_x96);
+    }
+
+    #[test]
+    fn rejects_unknown_venues() {
+        assert_eq!(Venue::from_u8(2), Some(Venue::Algebra));
+        assert_eq!(Venue::from_u8(3), None);
+        assert_eq!(UniswapV2.decode_amount_out(&[]), None);
+    }
+}
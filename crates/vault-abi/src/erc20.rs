@@ -0,0 +1,16 @@
+//! ERC-20 tokens held and traded by the vault
+
+// Imported packages
+use alloy_sol_types::sol;
+
+sol! {
+    /// The subset of ERC-20 the vault calls
+    interface IERC20 {
+        function balanceOf(address account) external view returns (uint256);
+        function transfer(address recipient, uint256 amount) external returns (bool);
+        function transferFrom(address sender, address recipient, uint256 amount) external returns (bool);
+        function approve(address spender, uint256 amount) external returns (bool);
+        function allowance(address owner, address spender) external view returns (uint256);
+        function decimals() external view returns (uint8);
+    }
+}
@@ -0,0 +1,19 @@
+//! Interfaces of the contracts the vault talks to
+//!
+//! Every external interface the vault calls (ERC-20 tokens, price oracles,
+//! swap routers, deposit receipts) is declared here once with `sol!`,
+//! together with the encoders and decoders built on top of them. The
+//! contracts, their native tests and off-chain tools all depend on this
+//! crate, so calldata is built the same way everywhere.
+//!
+//! The crate is `no_std` so that it compiles into the contract's WASM.
+#![no_std]
+extern crate alloc;
+
+pub mod dex;
+pub mod erc20;
+pub mod oracle;
+pub mod order;
+pub mod receipts;
+pub mod revert;
+pub mod router;
@@ -0,0 +1,11 @@
+//! Price oracles valuing the vault's basket
+
+// Imported packages
+use alloy_sol_types::sol;
+
+sol! {
+    /// Prices one whole token in USDC base units
+    interface IPriceOracle {
+        function getPrice(address token) external view returns (uint256);
+    }
+}
@@ -0,0 +1,194 @@
+//! EIP-712 rebalance orders
+//!
+//! The Token Metrics signer signs [`RebalanceOrder`]s off-chain and any
+//! relayer submits them to `rebalance_with_signature`. The vault hashes the
+//! order with [`signing_hash`], recovers the signer through the `ecrecover`
+//! precompile from [`ecrecover_input`] and checks it against the metric
+//! address, so the relayer itself holds no privileged key.
+//!
+//! Orders run through commit-reveal are committed as the [`commitment`]
+//! hash of a [`CommittedRebalance`], which keeps them private until they
+//! are revealed.
+
+// Imported packages
+use alloy_primitives::{address, keccak256, Address, B256, U256};
+use alloy_sol_types::{sol, Eip712Domain, SolStruct, SolValue};
+use alloc::borrow::Cow;
+
+/// Address of the `ecrecover` precompile
+pub const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+
+/// EIP-712 domain name of the vault
+pub const DOMAIN_NAME: &str = "Metric Vault";
+
+/// EIP-712 domain version of the vault
+pub const DOMAIN_VERSION: &str = "1";
+
+/// Half the order of secp256k1; signatures with a higher `s` are malleable
+const HALF_ORDER: U256 = U256::from_limbs([0xdfe92f46681b20a0, 0x5d576e7357a4501d, 0xffffffffffffffff, 0x7fffffffffffffff]);
+
+sol! {
+    /// Swaps the signer asks the vault to make: for each token, sell
+    /// `amountIn` of it for USDC when `zeroToOne`, else buy it with
+    /// `amountIn` USDC. Valid once, for the vault's next `nonce`, until
+    /// `deadline`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct RebalanceOrder {
+        address[] tokens;
+        bool[] zeroToOne;
+        uint256[] amountIn;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    /// A rebalance committed ahead of time; `salt` keeps the commitment
+    /// from being guessed.
+    #[derive(Debug, PartialEq, Eq)]
+    struct CommittedRebalance {
+        address[] tokens;
+        bool[] zeroToOne;
+        uint256[] amountIn;
+        bytes32 salt;
+    }
+}
+
+/// EIP-712 domain of the vault deployed at `vault` on `chain_id`
+pub fn domain(chain_id: u64, vault: Address) -> Eip712Domain {
+    Eip712Domain::new(
+        Some(Cow::Borrowed(DOMAIN_NAME)),
+        Some(Cow::Borrowed(DOMAIN_VERSION)),
+        Some(U256::from(chain_id)),
+        Some(vault),
+        None,
+    )
+}
+
+/// Digest the signer signs for `order` on the vault at `vault`
+pub fn signing_hash(order: &RebalanceOrder, chain_id: u64, vault: Address) -> B256 {
+    order.eip712_signing_hash(&domain(chain_id, vault))
+}
+
+/// Hash committed for `order`: the keccak256 of its ABI encoding
+pub fn commitment(order: &CommittedRebalance) -> B256 {
+    keccak256(order.abi_encode())
+}
+
+/// Input of the `ecrecover` precompile for a 65-byte `r || s || v`
+/// signature of `hash`. `v` may be 27/28 or 0/1; malleable signatures
+/// (high `s`) are rejected.
+pub fn ecrecover_input(hash: B256, signature: &[u8]) -> Option<[u8; 128]> {
+    if signature.len() != 65 {
+        return None;
+    }
+    let v = match signature[64] {
+        0 | 1 => signature[64] + 27,
+        27 | 28 => signature[64],
+        _ => return None,
+    };
+    if U256::from_be_slice(&signature[32..64]) > HALF_ORDER {
+        return None;
+    }
+
+    let mut input = [0u8; 128];
+    input[..32].copy_from_slice(hash.as_slice());
+    input[63] = v;
+    input[64..].copy_from_slice(&signature[..64]);
+    Some(input)
+}
+
+/// Signer returned by the `ecrecover` precompile, none if recovery failed
+pub fn decode_ecrecover(output: &[u8]) -> Option<Address> {
+    if output.len() != 32 || output[..12].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let signer = Address::from_slice(&output[12..]);
+    (signer != Address::ZERO).then_some(signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const VAULT: Address = address!("c3f7e1d0ab5b0f4b8d86f3a0d3c45f7b7e1c9a01");
+
+    fn order(nonce: u64) -> RebalanceOrder {
+        RebalanceOrder {
+            tokens: vec![address!("82af49447d8a07e3bd95bd0d56f35241523fbab1")],
+            zeroToOne: vec![true],
+            amountIn: vec![U256::from(1_000)],
+            nonce: U256::from(nonce),
+            deadline: U256::from(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn hashes_orders_with_the_vault_domain() {
+        assert_eq!(
+            RebalanceOrder::eip712_encode_type(),
+            "RebalanceOrder(address[] tokens,bool[] zeroToOne,uint256[] amountIn,uint256 nonce,uint256 deadline)"
+        );
+
+        // keccak256("\x19\x01" || domainSeparator || structHash)
+        let mut digest = vec![0x19, 0x01];
+        digest.extend_from_slice(domain(42_161, VAULT).separator().as_slice());
+        digest.extend_from_slice(order(0).eip712_hash_struct().as_slice());
+        assert_eq!(signing_hash(&order(0), 42_161, VAULT), keccak256(&digest));
+
+        // Orders are bound to their nonce, chain and vault
+        let hash = signing_hash(&order(0), 42_161, VAULT);
+        assert_ne!(hash, signing_hash(&order(1), 42_161, VAULT));
+        assert_ne!(hash, signing_hash(&order(0), 1, VAULT));
+        assert_ne!(hash, signing_hash(&order(0), 42_161, Address::ZERO));
+    }
+
+    #[test]
+    fn commits_to_the_whole_order() {
+        let committed = |salt: u8, amount: u64| CommittedRebalance {
+            tokens: vec![address!("82af49447d8a07e3bd95bd0d56f35241523fbab1")],
+            zeroToOne: vec![true],
+            amountIn: vec![U256::from(amount)],
+            salt: B256::repeat_byte(salt),
+        };
+        let hash = commitment(&committed(1, 1_000));
+        assert_eq!(hash, keccak256(committed(1, 1_000).abi_encode()));
+        assert_ne!(hash, commitment(&committed(2, 1_000)));
+        assert_ne!(hash, commitment(&committed(1, 1_001)));
+    }
+
+    #[test]
+    fn builds_ecrecover_input() {
+        let hash = B256::repeat_byte(0xaa);
+        let mut signature = [0u8; 65];
+        signature[..32].fill(0x11);
+        signature[32..64].fill(0x22);
+        signature[64] = 1;
+
+        let input = ecrecover_input(hash, &signature).unwrap();
+        assert_eq!(&input[..32], hash.as_slice());
+        assert_eq!(U256::from_be_slice(&input[32..64]), U256::from(28));
+        assert_eq!(&input[64..96], &[0x11; 32]);
+        assert_eq!(&input[96..], &[0x22; 32]);
+
+        signature[64] = 29;
+        assert_eq!(ecrecover_input(hash, &signature), None);
+        assert_eq!(ecrecover_input(hash, &signature[..64]), None);
+
+        // High s values have a twin signature and are refused
+        signature[64] = 27;
+        signature[32..64].fill(0xff);
+        assert_eq!(ecrecover_input(hash, &signature), None);
+    }
+
+    #[test]
+    fn decodes_recovered_signers() {
+        let mut output = [0u8; 32];
+        assert_eq!(decode_ecrecover(&output), None);
+        assert_eq!(decode_ecrecover(&[]), None);
+
+        output[12..].copy_from_slice(VAULT.as_slice());
+        assert_eq!(decode_ecrecover(&output), Some(VAULT));
+        output[0] = 1;
+        assert_eq!(decode_ecrecover(&output), None);
+    }
+}
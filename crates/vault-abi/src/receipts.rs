@@ -0,0 +1,62 @@
+//! Deposit receipts and the vault's side of them
+//!
+//! In receipt accounting mode the vault records every deposit as an ERC-721
+//! receipt of its own contract, so wallets and indexers see a standard
+//! ERC-721 apart from the vault's ERC-20 shares. Only the vault mints and
+//! burns receipts, and the receipts contract reports every transfer back to
+//! the vault, which moves the shares the receipt records along with it.
+
+// Imported packages
+use alloy_primitives::{fixed_bytes, FixedBytes};
+use alloy_sol_types::sol;
+
+/// ERC-165 interface id of ERC-165 itself
+pub const ERC165_INTERFACE_ID: FixedBytes<4> = fixed_bytes!("01ffc9a7");
+
+/// ERC-165 interface id of ERC-721
+pub const ERC721_INTERFACE_ID: FixedBytes<4> = fixed_bytes!("80ac58cd");
+
+/// ERC-165 interface id of the ERC-721 metadata extension
+pub const ERC721_METADATA_INTERFACE_ID: FixedBytes<4> = fixed_bytes!("5b5e139f");
+
+sol! {
+    /// What the vault calls on the receipts contract
+    interface IReceipts {
+        function initialize() external;
+        function mint(address to) external returns (uint256);
+        function burn(uint256 tokenId) external;
+        function ownerOf(uint256 tokenId) external view returns (address);
+    }
+
+    /// What the receipts contract calls on the vault
+    interface IReceiptVault {
+        function onReceiptTransfer(address from, address to, uint256 receiptId) external;
+    }
+
+    /// Contracts accepting safe ERC-721 transfers
+    interface IERC721Receiver {
+        function onERC721Received(address operator, address from, uint256 tokenId, bytes data) external returns (bytes4);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::SolCall;
+
+    #[test]
+    fn uses_the_standard_selectors() {
+        assert_eq!(IERC721Receiver::onERC721ReceivedCall::SELECTOR, [0x15, 0x0b, 0x7a, 0x02]);
+        assert_eq!(IReceipts::ownerOfCall::SELECTOR, [0x63, 0x52, 0x21, 0x1e]);
+
+        // The ERC-721 id is the xor of its nine function selectors
+        let selectors = [
+            "70a08231", "6352211e", "42842e0e", "b88d4fde", "23b872dd", "095ea7b3", "a22cb465", "081812fc", "e985e9c5",
+        ];
+        let id = selectors
+            .iter()
+            .map(|selector| u32::from_str_radix(selector, 16).unwrap())
+            .fold(0, |id, selector| id ^ selector);
+        assert_eq!(id.to_be_bytes(), ERC721_INTERFACE_ID.0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Decoding of revert data returned by failed external calls
+//!
+//! Routers and tokens revert with `Error(string)` (`require` messages such
+//! as Uniswap's `"STF"` or `"Too little received"`), with `Panic(uint256)`,
+//! or with custom errors. [`decode_revert`] turns the raw data into a
+//! [`RevertReason`] that can be logged as a readable string.
+
+// Imported packages
+use alloc::{string::String, vec::Vec};
+use alloy_primitives::{hex, U256};
+use alloy_sol_types::{sol, Panic, Revert, SolError};
+use core::fmt;
+
+sol! {
+    // OpenZeppelin 5 ERC-20 errors
+    error ERC20InsufficientBalance(address sender, uint256 balance, uint256 needed);
+    error ERC20InsufficientAllowance(address spender, uint256 allowance, uint256 needed);
+    error ERC20InvalidSender(address sender);
+    error ERC20InvalidReceiver(address receiver);
+    error ERC20InvalidApprover(address approver);
+    error ERC20InvalidSpender(address spender);
+
+    // Errors of the Stylus ERC-20 template
+    error InsufficientBalance(address from, uint256 have, uint256 want);
+    error InsufficientAllowance(address owner, address spender, uint256 have, uint256 want);
+}
+
+/// Selectors and names of the custom errors [`decode_revert`] recognizes
+const KNOWN_ERRORS: [([u8; 4], &str); 8] = [
+    (ERC20InsufficientBalance::SELECTOR, ERC20InsufficientBalance::SIGNATURE),
+    (ERC20InsufficientAllowance::SELECTOR, ERC20InsufficientAllowance::SIGNATURE),
+    (ERC20InvalidSender::SELECTOR, ERC20InvalidSender::SIGNATURE),
+    (ERC20InvalidReceiver::SELECTOR, ERC20InvalidReceiver::SIGNATURE),
+    (ERC20InvalidApprover::SELECTOR, ERC20InvalidApprover::SIGNATURE),
+    (ERC20InvalidSpender::SELECTOR, ERC20InvalidSpender::SIGNATURE),
+    (InsufficientBalance::SELECTOR, InsufficientBalance::SIGNATURE),
+    (InsufficientAllowance::SELECTOR, InsufficientAllowance::SIGNATURE),
+];
+
+/// Why an external call failed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevertReason {
+    /// The call reverted without data, e.g. it ran out of gas or hit a
+    /// contract-less address
+    Empty,
+    /// `Error(string)`, raised by `require` and `revert("...")`
+    Error(String),
+    /// `Panic(uint256)`, raised by failed asserts and checked arithmetic
+    Panic(U256),
+    /// A known custom error, by signature
+    Custom(&'static str),
+    /// Data matching nothing known
+    Unknown(Vec<u8>),
+}
+
+/// Decodes the revert data of a failed call
+pub fn decode_revert(data: &[u8]) -> RevertReason {
+    if data.is_empty() {
+        return RevertReason::Empty;
+    }
+    if let Ok(revert) = Revert::abi_decode(data, true) {
+        return RevertReason::Error(revert.reason);
+    }
+    if let Ok(panic) = Panic::abi_decode(data, true) {
+        return RevertReason::Panic(panic.code);
+    }
+    KNOWN_ERRORS
+        .iter()
+        .find(|(selector, _)| data.starts_with(selector))
+        .map_or_else(|| RevertReason::Unknown(data.to_vec()), |(_, signature)| RevertReason::Custom(signature))
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("reverted without a reason"),
+            Self::Error(reason) => f.write_str(reason),
+            Self::Panic(code) => match (Panic { code: *code }).kind() {
+                Some(kind) => write!(f, "panic: {}", kind),
+                None => write!(f, "panic: {:#x}", code),
+            },
+            Self::Custom(signature) => f.write_str(signature),
+            Self::Unknown(data) => write!(f, "0x{}", hex::encode(data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloy_primitives::address;
+
+    #[test]
+    fn decodes_error_strings() {
+        let data = Revert::from("Too little received").abi_encode();
+        assert_eq!(decode_revert(&data), RevertReason::Error("Too little received".into()));
+        assert_eq!(decode_revert(&data).to_string(), "Too little received");
+    }
+
+    #[test]
+    fn decodes_panics() {
+        let data = Panic { code: U256::from(0x11) }.abi_encode();
+        assert_eq!(decode_revert(&data), RevertReason::Panic(U256::from(0x11)));
+        assert_eq!(decode_revert(&data).to_string(), "panic: arithmetic underflow or overflow");
+        assert_eq!(RevertReason::Panic(U256::from(0x99)).to_string(), "panic: 0x99");
+    }
+
+    #[test]
+    fn decodes_known_custom_errors() {
+        let spender = address!("bebbe2bacc1f5caf9a471838b7567ff636093c84");
+        let data = ERC20InsufficientAllowance { spender, allowance: U256::ZERO, needed: U256::from(1) }.abi_encode();
+        assert_eq!(
+            decode_revert(&data).to_string(),
+            "ERC20InsufficientAllowance(address,uint256,uint256)"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_data() {
+        assert_eq!(decode_revert(&[]), RevertReason::Empty);
+        assert_eq!(decode_revert(&[0xde, 0xad, 0xbe, 0xef]).to_string(), "0xdeadbeef");
+    }
+}
@@ -0,0 +1,549 @@
+//! Typed calldata for the Uniswap V3 swap routers
+//!
+//! Router calls are declared with `sol!` and encoded with [`SolCall`], so the
+//! selector and struct layout come from the Solidity signature rather than
+//! being assembled byte by byte.
+//!
+//! Uniswap ships two routers whose swap params differ: the original
+//! `SwapRouter` takes a `deadline` in every params struct, `SwapRouter02`
+//! does not. [`RouterKind`] encodes each swap for the router in use.
+//!
+//! Multi-pool swaps take a packed path of tokens and the fee tiers of the
+//! pools between them, built with [`encode_path`].
+
+// Imported packages
+use alloc::{vec, vec::Vec};
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_sol_types::{sol, SolCall};
+
+sol! {
+    /// Uniswap V3 `SwapRouter`, whose swap params carry a deadline
+    interface ISwapRouter {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 deadline;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        struct ExactInputParams {
+            bytes path;
+            address recipient;
+            uint256 deadline;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+        }
+
+        struct ExactOutputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 deadline;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        struct ExactOutputParams {
+            bytes path;
+            address recipient;
+            uint256 deadline;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut);
+        function exactInput(ExactInputParams calldata params) external payable returns (uint256 amountOut);
+        function exactOutputSingle(ExactOutputSingleParams calldata params) external payable returns (uint256 amountIn);
+        function exactOutput(ExactOutputParams calldata params) external payable returns (uint256 amountIn);
+    }
+}
+
+// A separate block, as `sol!` resolves struct names across the whole block
+sol! {
+    /// Uniswap V3 `SwapRouter02`, whose swap params carry no deadline
+    interface ISwapRouter02 {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        struct ExactInputParams {
+            bytes path;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+        }
+
+        struct ExactOutputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        struct ExactOutputParams {
+            bytes path;
+            address recipient;
+            uint256 amountOut;
+            uint256 amountInMaximum;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut);
+        function exactInput(ExactInputParams calldata params) external payable returns (uint256 amountOut);
+        function exactOutputSingle(ExactOutputSingleParams calldata params) external payable returns (uint256 amountIn);
+        function exactOutput(ExactOutputParams calldata params) external payable returns (uint256 amountIn);
+    }
+}
+
+/// A single-pool swap of an exact `amount_in`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactInputSingle {
+    pub token_in: Address,
+    pub token_out: Address,
+    /// Pool fee tier in hundredths of a bip
+    pub fee: u32,
+    pub recipient: Address,
+    pub amount_in: U256,
+    pub amount_out_minimum: U256,
+    /// Price bound of the swap, zero for none
+    pub sqrt_price_limit_x96: U256,
+}
+
+/// A multi-pool swap of an exact `amount_in` along a packed `path`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactInput {
+    pub path: Vec<u8>,
+    pub recipient: Address,
+    pub amount_in: U256,
+    pub amount_out_minimum: U256,
+}
+
+/// A single-pool swap for an exact `amount_out`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactOutputSingle {
+    pub token_in: Address,
+    pub token_out: Address,
+    /// Pool fee tier in hundredths of a bip
+    pub fee: u32,
+    pub recipient: Address,
+    pub amount_out: U256,
+    pub amount_in_maximum: U256,
+    /// Price bound of the swap, zero for none
+    pub sqrt_price_limit_x96: U256,
+}
+
+/// A multi-pool swap for an exact `amount_out` along a packed `path`,
+/// which runs from the output token back to the input token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactOutput {
+    pub path: Vec<u8>,
+    pub recipient: Address,
+    pub amount_out: U256,
+    pub amount_in_maximum: U256,
+}
+
+/// Byte length of a token in a packed path
+const ADDRESS_SIZE: usize = 20;
+
+/// Byte length of a fee tier in a packed path
+const FEE_SIZE: usize = 3;
+
+/// Packs `tokens` and the `fees` of the pools between them into a path
+/// (`token, fee, token, ..., fee, token`). Returns `None` unless there is
+/// exactly one fee per hop and every fee fits in a `uint24`.
+pub fn encode_path(tokens: &[Address], fees: &[u32]) -> Option<Vec<u8>> {
+    if tokens.len() < 2 || fees.len() + 1 != tokens.len() || fees.iter().any(|fee| *fee >= 1 << 24) {
+        return None;
+    }
+
+    let mut path = Vec::with_capacity(tokens.len() * ADDRESS_SIZE + fees.len() * FEE_SIZE);
+    path.extend_from_slice(tokens[0].as_slice());
+    for (fee, token) in fees.iter().zip(&tokens[1..]) {
+        path.extend_from_slice(&fee.to_be_bytes()[1..]);
+        path.extend_from_slice(token.as_slice());
+    }
+    Some(path)
+}
+
+/// Unpacks a path into its tokens and fees, or `None` if it is malformed
+pub fn decode_path(path: &[u8]) -> Option<(Vec<Address>, Vec<u32>)> {
+    let hop = ADDRESS_SIZE + FEE_SIZE;
+    if path.len() < ADDRESS_SIZE + hop || (path.len() - ADDRESS_SIZE) % hop != 0 {
+        return None;
+    }
+
+    let mut tokens = vec![Address::from_slice(&path[..ADDRESS_SIZE])];
+    let mut fees = Vec::new();
+    for chunk in path[ADDRESS_SIZE..].chunks(hop) {
+        fees.push(u32::from_be_bytes([0, chunk[0], chunk[1], chunk[2]]));
+        tokens.push(Address::from_slice(&chunk[FEE_SIZE..]));
+    }
+    Some((tokens, fees))
+}
+
+/// Reverses a path, turning the `exactInput` path of a swap into the
+/// `exactOutput` path of the same swap, which runs from output to input
+pub fn reverse_path(path: &[u8]) -> Option<Vec<u8>> {
+    let (mut tokens, mut fees) = decode_path(path)?;
+    tokens.reverse();
+    fees.reverse();
+    encode_path(&tokens, &fees)
+}
+
+/// Joins two paths end to end, e.g. a token's path to USDC with another
+/// token's path from USDC. Returns `None` unless `first` ends with the
+/// token `second` starts with.
+pub fn join_paths(first: &[u8], second: &[u8]) -> Option<Vec<u8>> {
+    let (mut tokens, mut fees) = decode_path(first)?;
+    let (more_tokens, more_fees) = decode_path(second)?;
+    if tokens.last() != more_tokens.first() {
+        return None;
+    }
+    tokens.extend_from_slice(&more_tokens[1..]);
+    fees.extend(more_fees);
+    encode_path(&tokens, &fees)
+}
+
+/// Which Uniswap V3 router the vault talks to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouterKind {
+    /// `SwapRouter02`, the router on Arbitrum Sepolia
+    SwapRouter02 = 0,
+    /// The original `SwapRouter`
+    SwapRouter = 1,
+}
+
+impl RouterKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(Self::SwapRouter02),
+            1 => Some(Self::SwapRouter),
+            _ => None,
+        }
+    }
+
+    /// Calldata for `exactInputSingle`; `deadline` is dropped by `SwapRouter02`
+    pub fn encode_exact_input_single(self, swap: &ExactInputSingle, deadline: U256) -> Vec<u8> {
+        match self {
+            Self::SwapRouter => ISwapRouter::exactInputSingleCall {
+                params: ISwapRouter::ExactInputSingleParams {
+                    tokenIn: swap.token_in,
+                    tokenOut: swap.token_out,
+                    fee: swap.fee,
+                    recipient: swap.recipient,
+                    deadline,
+                    amountIn: swap.amount_in,
+                    amountOutMinimum: swap.amount_out_minimum,
+                    sqrtPriceLimitX96: swap.sqrt_price_limit_x96,
+                },
+            }
+            .abi_encode(),
+            Self::SwapRouter02 => ISwapRouter02::exactInputSingleCall {
+                params: ISwapRouter02::ExactInputSingleParams {
+                    tokenIn: swap.token_in,
+                    tokenOut: swap.token_out,
+                    fee: swap.fee,
+                    recipient: swap.recipient,
+                    amountIn: swap.amount_in,
+                    amountOutMinimum: swap.amount_out_minimum,
+                    sqrtPriceLimitX96: swap.sqrt_price_limit_x96,
+                },
+            }
+            .abi_encode(),
+        }
+    }
+
+    /// Calldata for `exactInput`; `deadline` is dropped by `SwapRouter02`
+    pub fn encode_exact_input(self, swap: &ExactInput, deadline: U256) -> Vec<u8> {
+        let path = Bytes::copy_from_slice(&swap.path);
+        match self {
+            Self::SwapRouter => ISwapRouter::exactInputCall {
+                params: ISwapRouter::ExactInputParams {
+                    path,
+                    recipient: swap.recipient,
+                    deadline,
+                    amountIn: swap.amount_in,
+                    amountOutMinimum: swap.amount_out_minimum,
+                },
+            }
+            .abi_encode(),
+            Self::SwapRouter02 => ISwapRouter02::exactInputCall {
+                params: ISwapRouter02::ExactInputParams {
+                    path,
+                    recipient: swap.recipient,
+                    amountIn: swap.amount_in,
+                    amountOutMinimum: swap.amount_out_minimum,
+                },
+            }
+            .abi_encode(),
+        }
+    }
+
+    /// Calldata for `exactOutputSingle`; `deadline` is dropped by `SwapRouter02`
+    pub fn encode_exact_output_single(self, swap: &ExactOutputSingle, deadline: U256) -> Vec<u8> {
+        match self {
+            Self::SwapRouter => ISwapRouter::exactOutputSingleCall {
+                params: ISwapRouter::ExactOutputSingleParams {
+                    tokenIn: swap.token_in,
+                    tokenOut: swap.token_out,
+                    fee: swap.fee,
+                    recipient: swap.recipient,
+                    deadline,
+                    amountOut: swap.amount_out,
+                    amountInMaximum: swap.amount_in_maximum,
+                    sqrtPriceLimitX96: swap.sqrt_price_limit_x96,
+                },
+            }
+            .abi_encode(),
+            Self::SwapRouter02 => ISwapRouter02::exactOutputSingleCall {
+                params: ISwapRouter02::ExactOutputSingleParams {
+                    tokenIn: swap.token_in,
+                    tokenOut: swap.token_out,
+                    fee: swap.fee,
+                    recipient: swap.recipient,
+                    amountOut: swap.amount_out,
+                    amountInMaximum: swap.amount_in_maximum,
+                    sqrtPriceLimitX96: swap.sqrt_price_limit_x96,
+                },
+            }
+            .abi_encode(),
+        }
+    }
+
+    /// Calldata for `exactOutput`; `deadline` is dropped by `SwapRouter02`
+    pub fn encode_exact_output(self, swap: &ExactOutput, deadline: U256) -> Vec<u8> {
+        let path = Bytes::copy_from_slice(&swap.path);
+        match self {
+            Self::SwapRouter => ISwapRouter::exactOutputCall {
+                params: ISwapRouter::ExactOutputParams {
+                    path,
+                    recipient: swap.recipient,
+                    deadline,
+                    amountOut: swap.amount_out,
+                    amountInMaximum: swap.amount_in_maximum,
+                },
+            }
+            .abi_encode(),
+            Self::SwapRouter02 => ISwapRouter02::exactOutputCall {
+                params: ISwapRouter02::ExactOutputParams {
+                    path,
+                    recipient: swap.recipient,
+                    amountOut: swap.amount_out,
+                    amountInMaximum: swap.amount_in_maximum,
+                },
+            }
+            .abi_encode(),
+        }
+    }
+}
+
+/// Reads `amountOut` from the data returned by `exactInputSingle` or
+/// `exactInput`, which is the same for both routers
+pub fn decode_amount_out(return_data: &[u8]) -> Option<U256> {
+    ISwapRouter02::exactInputSingleCall::abi_decode_returns(return_data, true)
+        .ok()
+        .map(|ret| ret.amountOut)
+}
+
+/// Reads `amountIn` from the data returned by `exactOutputSingle` or
+/// `exactOutput`, which is the same for both routers
+pub fn decode_amount_in(return_data: &[u8]) -> Option<U256> {
+    ISwapRouter02::exactOutputSingleCall::abi_decode_returns(return_data, true)
+        .ok()
+        .map(|ret| ret.amountIn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, hex};
+
+    const DEADLINE: U256 = U256::from_limbs([1_700_000_000, 0, 0, 0]);
+
+    fn swap() -> ExactInputSingle {
+        ExactInputSingle {
+            token_in: address!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d"),
+            token_out: address!("980b62da83eff3d4576c647993b0c1d7faf17c73"),
+            fee: 3000,
+            recipient: address!("bebbe2bacc1f5caf9a471838b7567ff636093c84"),
+            amount_in: U256::from(100),
+            amount_out_minimum: U256::from(100),
+            sqrt_price_limit_x96: U256::ZERO,
+        }
+    }
+
+    fn path() -> Vec<u8> {
+        hex!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d000bb8980b62da83eff3d4576c647993b0c1d7faf17c73").to_vec()
+    }
+
+    #[test]
+    fn uses_each_routers_selectors() {
+        let single = swap();
+        let input = ExactInput { path: path(), recipient: single.recipient, amount_in: U256::from(1), amount_out_minimum: U256::ZERO };
+        let output_single = ExactOutputSingle {
+            token_in: single.token_in,
+            token_out: single.token_out,
+            fee: single.fee,
+            recipient: single.recipient,
+            amount_out: U256::from(1),
+            amount_in_maximum: U256::MAX,
+            sqrt_price_limit_x96: U256::ZERO,
+        };
+        let output = ExactOutput { path: path(), recipient: single.recipient, amount_out: U256::from(1), amount_in_maximum: U256::MAX };
+
+        let selectors = |kind: RouterKind| {
+            [
+                kind.encode_exact_input_single(&single, DEADLINE),
+                kind.encode_exact_input(&input, DEADLINE),
+                kind.encode_exact_output_single(&output_single, DEADLINE),
+                kind.encode_exact_output(&output, DEADLINE),
+            ]
+            .map(|calldata| hex::encode(&calldata[..4]))
+        };
+
+        assert_eq!(selectors(RouterKind::SwapRouter02), ["04e45aaf", "b858183f", "5023b4df", "09b81346"]);
+        assert_eq!(selectors(RouterKind::SwapRouter), ["414bf389", "c04b8d59", "db3e2198", "f28c0498"]);
+    }
+
+    #[test]
+    fn matches_the_deployed_calldata() {
+        // Selector followed by the seven params, each padded to a word
+        let expected = hex!(
+            "04e45aaf"
+            "00000000000000000000000075faf114eafb1bdbe2f0316df893fd58ce46aa4d"
+            "000000000000000000000000980b62da83eff3d4576c647993b0c1d7faf17c73"
+            "0000000000000000000000000000000000000000000000000000000000000bb8"
+            "000000000000000000000000bebbe2bacc1f5caf9a471838b7567ff636093c84"
+            "0000000000000000000000000000000000000000000000000000000000000064"
+            "0000000000000000000000000000000000000000000000000000000000000064"
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(RouterKind::SwapRouter02.encode_exact_input_single(&swap(), DEADLINE), expected);
+    }
+
+    #[test]
+    fn round_trips_through_the_router_abi() {
+        let swap = swap();
+
+        let call = ISwapRouter02::exactInputSingleCall::abi_decode(
+            &RouterKind::SwapRouter02.encode_exact_input_single(&swap, DEADLINE),
+            true,
+        )
+        .unwrap();
+        assert_eq!(call.params.tokenIn, swap.token_in);
+        assert_eq!(call.params.tokenOut, swap.token_out);
+        assert_eq!(call.params.fee, swap.fee);
+        assert_eq!(call.params.recipient, swap.recipient);
+        assert_eq!(call.params.amountIn, swap.amount_in);
+        assert_eq!(call.params.amountOutMinimum, swap.amount_out_minimum);
+        assert_eq!(call.params.sqrtPriceLimitX96, swap.sqrt_price_limit_x96);
+
+        let call = ISwapRouter::exactInputSingleCall::abi_decode(
+            &RouterKind::SwapRouter.encode_exact_input_single(&swap, DEADLINE),
+            true,
+        )
+        .unwrap();
+        assert_eq!(call.params.deadline, DEADLINE);
+        assert_eq!(call.params.amountIn, swap.amount_in);
+        assert_eq!(call.params.amountOutMinimum, swap.amount_out_minimum);
+    }
+
+    #[test]
+    fn round_trips_paths() {
+        let swap = ExactOutput { path: path(), recipient: Address::ZERO, amount_out: U256::from(5), amount_in_maximum: U256::from(9) };
+
+        let call = ISwapRouter::exactOutputCall::abi_decode(&RouterKind::SwapRouter.encode_exact_output(&swap, DEADLINE), true).unwrap();
+        assert_eq!(call.params.path.to_vec(), swap.path);
+        assert_eq!(call.params.deadline, DEADLINE);
+        assert_eq!(call.params.amountInMaximum, swap.amount_in_maximum);
+
+        let call = ISwapRouter02::exactOutputCall::abi_decode(&RouterKind::SwapRouter02.encode_exact_output(&swap, DEADLINE), true).unwrap();
+        assert_eq!(call.params.path.to_vec(), swap.path);
+        assert_eq!(call.params.amountOut, swap.amount_out);
+    }
+
+    #[test]
+    fn flavours_do_not_decode_each_other() {
+        let calldata = RouterKind::SwapRouter.encode_exact_input_single(&swap(), DEADLINE);
+        assert!(ISwapRouter02::exactInputSingleCall::abi_decode(&calldata, true).is_err());
+
+        // Past the selector, the extra deadline word shifts every later field
+        let call = ISwapRouter02::exactInputSingleCall::abi_decode_raw(&calldata[4..], false).unwrap();
+        assert_eq!(call.params.amountIn, DEADLINE);
+    }
+
+    #[test]
+    fn packs_paths() {
+        let (tokens, fees) = decode_path(&path()).unwrap();
+        assert_eq!(tokens, vec![swap().token_in, swap().token_out]);
+        assert_eq!(fees, vec![3000]);
+        assert_eq!(encode_path(&tokens, &fees).unwrap(), path());
+
+        // Two hops through WETH
+        let weth = address!("980b62da83eff3d4576c647993b0c1d7faf17c73");
+        let tokens = [swap().token_in, weth, swap().recipient];
+        let path = encode_path(&tokens, &[500, 10_000]).unwrap();
+        assert_eq!(path.len(), 20 * 3 + 3 * 2);
+        assert_eq!(&path[20..23], &hex!("0001f4"));
+        assert_eq!(&path[43..46], &hex!("002710"));
+        assert_eq!(decode_path(&path), Some((tokens.to_vec(), vec![500, 10_000])));
+    }
+
+    #[test]
+    fn reverses_paths() {
+        let weth = address!("980b62da83eff3d4576c647993b0c1d7faf17c73");
+        let tokens = [swap().token_in, weth, swap().recipient];
+        let path = encode_path(&tokens, &[500, 10_000]).unwrap();
+
+        let reversed = reverse_path(&path).unwrap();
+        assert_eq!(decode_path(&reversed), Some((vec![swap().recipient, weth, swap().token_in], vec![10_000, 500])));
+        assert_eq!(reverse_path(&reversed), Some(path));
+        assert_eq!(reverse_path(&[0; 21]), None);
+    }
+
+    #[test]
+    fn joins_paths() {
+        let weth = address!("980b62da83eff3d4576c647993b0c1d7faf17c73");
+        let to_weth = encode_path(&[swap().token_in, weth], &[500]).unwrap();
+        let from_weth = encode_path(&[weth, swap().recipient], &[3000]).unwrap();
+
+        let joined = join_paths(&to_weth, &from_weth).unwrap();
+        assert_eq!(decode_path(&joined), Some((vec![swap().token_in, weth, swap().recipient], vec![500, 3000])));
+        assert_eq!(join_paths(&from_weth, &to_weth), None);
+        assert_eq!(join_paths(&to_weth, &[]), None);
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        let tokens = [swap().token_in, swap().token_out];
+        assert_eq!(encode_path(&tokens[..1], &[]), None);
+        assert_eq!(encode_path(&tokens, &[]), None);
+        assert_eq!(encode_path(&tokens, &[1 << 24]), None);
+
+        assert_eq!(decode_path(&path()[..20]), None);
+        assert_eq!(decode_path(&path()[..42]), None);
+        assert_eq!(decode_path(&[path(), vec![0; 3]].concat()), None);
+    }
+
+    #[test]
+    fn decodes_returned_amounts() {
+        let returned = ISwapRouter02::exactInputSingleCall::abi_encode_returns(&(U256::from(42),));
+        assert_eq!(decode_amount_out(&returned), Some(U256::from(42)));
+        assert_eq!(decode_amount_in(&returned), Some(U256::from(42)));
+        assert_eq!(decode_amount_out(&[0u8; 4]), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Configuration of the tokens the vault trades
+//!
+//! Every basket token has a [`TokenConfig`]: the fee tier of its USDC pool,
+//! its decimals, the oracle pricing it and the largest share of the vault
+//! it may make up. Disabled tokens stay in the basket, so what the vault
+//! still holds of them is valued and redeemed, but rebalances stop buying
+//! them.
+
+// Imported packages
+use alloy_primitives::{Address, Uint, U16, U256, U8};
+use crate::tax::BPS_DENOMINATOR;
+use stylus_sdk::prelude::*;
+
+/// Fee tier of the USDC pool of tokens without a config (0.3%)
+pub const DEFAULT_FEE: u32 = 3000;
+
+sol_storage! {
+    /// Trading settings of a single basket token.
+    pub struct TokenConfig {
+        address token;
+        /// Fee tier of the token's USDC pool, in hundredths of a bip
+        uint24 fee;
+        uint8 decimals;
+        /// Oracle pricing the token, zero for the vault's oracle
+        address oracle;
+        /// Largest share of the vault the token may make up, in bps
+        uint16 max_weight_bps;
+        bool enabled;
+    }
+
+    /// Basket keeps the config of every token the vault trades.
+    pub struct Basket {
+        /// Configs in the order tokens were added
+        TokenConfig[] configs;
+        /// Maps tokens to their index in `configs` plus one, zero when absent
+        mapping(address => uint256) positions;
+    }
+}
+
+/// A token config read out of storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenSettings {
+    pub token: Address,
+    pub fee: u32,
+    pub decimals: u8,
+    pub oracle: Address,
+    pub max_weight_bps: u16,
+    pub enabled: bool,
+}
+
+impl TokenSettings {
+    /// Settings for `token` with the default fee tier and no weight limit
+    pub fn new(token: Address, decimals: u8) -> Self {
+        Self {
+            token,
+            fee: DEFAULT_FEE,
+            decimals,
+            oracle: Address::ZERO,
+            max_weight_bps: BPS_DENOMINATOR,
+            enabled: true,
+        }
+    }
+
+    /// Whether the fee fits a `uint24` and the weight is at most 100%
+    pub fn is_valid(&self) -> bool {
+        self.token != Address::ZERO && self.fee < 1 << 24 && self.max_weight_bps <= BPS_DENOMINATOR
+    }
+}
+
+impl Basket {
+    /// Number of tokens in the basket, enabled or not
+    pub fn len(&self) -> usize {
+        self.configs.len()
+    }
+
+    /// Config of the `index`th token of the basket
+    pub fn at(&self, index: usize) -> Option<TokenSettings> {
+        let config = self.configs.get(index)?;
+        Some(TokenSettings {
+            token: config.token.get(),
+            fee: config.fee.get().to::<u32>(),
+            decimals: config.decimals.get().to::<u8>(),
+            oracle: config.oracle.get(),
+            max_weight_bps: config.max_weight_bps.get().to::<u16>(),
+            enabled: config.enabled.get(),
+        })
+    }
+
+    /// Config of `token`, if it is in the basket
+    pub fn get(&self, token: Address) -> Option<TokenSettings> {
+        let position = self.positions.get(token).to::<usize>();
+        position.checked_sub(1).and_then(|index| self.at(index))
+    }
+
+    /// Configs of every token in the basket
+    pub fn configs(&self) -> Vec<TokenSettings> {
+        (0..self.len()).filter_map(|index| self.at(index)).collect()
+    }
+
+    /// Every token in the basket, enabled or not
+    pub fn tokens(&self) -> Vec<Address> {
+        self.configs().into_iter().map(|config| config.token).collect()
+    }
+
+    /// Adds `settings.token` to the basket, or updates its config
+    pub fn set(&mut self, settings: TokenSettings) {
+        let position = self.positions.get(settings.token).to::<usize>();
+        let mut config = match position.checked_sub(1).and_then(|index| self.configs.setter(index)) {
+            Some(config) => config,
+            None => {
+                self.positions.insert(settings.token, U256::from(self.configs.len() + 1));
+                self.configs.grow()
+            }
+        };
+        config.token.set(settings.token);
+        config.fee.set(Uint::<24, 1>::from(settings.fee));
+        config.decimals.set(U8::from(settings.decimals));
+        config.oracle.set(settings.oracle);
+        config.max_weight_bps.set(U16::from(settings.max_weight_bps));
+        config.enabled.set(settings.enabled);
+    }
+
+    /// Enables or disables `token`; returns false if it is not in the basket
+    pub fn set_enabled(&mut self, token: Address, enabled: bool) -> bool {
+        let position = self.positions.get(token).to::<usize>();
+        match position.checked_sub(1).and_then(|index| self.configs.setter(index)) {
+            Some(mut config) => {
+                config.enabled.set(enabled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disables every token of the basket
+    pub fn disable_all(&mut self) {
+        for index in 0..self.configs.len() {
+            if let Some(mut config) = self.configs.setter(index) {
+                config.enabled.set(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    #[test]
+    fn validates_settings() {
+        let token = address!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d");
+        let settings = TokenSettings::new(token, 18);
+        assert!(settings.is_valid());
+        assert_eq!(settings.fee, DEFAULT_FEE);
+        assert_eq!(settings.max_weight_bps, BPS_DENOMINATOR);
+
+        assert!(!TokenSettings { fee: 1 << 24, ..settings }.is_valid());
+        assert!(!TokenSettings { max_weight_bps: BPS_DENOMINATOR + 1, ..settings }.is_valid());
+        assert!(!TokenSettings::new(Address::ZERO, 18).is_valid());
+    }
+}
@@ -0,0 +1,181 @@
+//! CSV exports of a replayed ledger
+
+use std::io::{self, Write};
+
+use alloy_primitives::{utils::format_units, Address, U256};
+
+use crate::ledger::{ActivityKind, Ledger, Point};
+
+/// Holding period after which a gain is long-term
+const LONG_TERM_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+/// Ticker used for vault shares in the exports
+const SHARE_SYMBOL: &str = "METRIC";
+
+/// Ticker used for the vault's asset in the exports
+const ASSET_SYMBOL: &str = "USDC";
+
+/// The layouts `taxmate-report` can write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One row per disposed lot, in IRS Form 8949 columns
+    Form8949,
+    /// One row per holder movement, for import into crypto tax software
+    Generic,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "form8949" | "8949" => Ok(Format::Form8949),
+            "generic" => Ok(Format::Generic),
+            other => Err(format!("unknown format `{}`", other)),
+        }
+    }
+}
+
+/// Decimals used to print amounts
+#[derive(Clone, Copy, Debug)]
+pub struct Units {
+    pub assets: u8,
+    pub shares: u8,
+}
+
+pub fn write_csv(
+    out: &mut impl Write,
+    ledger: &Ledger,
+    format: Format,
+    owner: Option<Address>,
+    units: Units,
+) -> io::Result<()> {
+    let selected = |address: Address| owner.map_or(true, |owner| owner == address);
+
+    match format {
+        Format::Form8949 => {
+            writeln!(
+                out,
+                "Part,Description,Date Acquired,Date Sold,Proceeds,Cost Basis,Code,Adjustment,Gain or Loss,Owner,Transaction"
+            )?;
+            for disposal in ledger.disposals.iter().filter(|d| selected(d.owner)) {
+                let part = match (disposal.acquired.and_then(|p| p.timestamp), disposal.sold.timestamp) {
+                    (Some(acquired), Some(sold)) if sold - acquired > LONG_TERM_SECONDS => "II",
+                    (Some(_), Some(_)) => "I",
+                    _ => "",
+                };
+                writeln!(
+                    out,
+                    "{},{} {},{},{},{},{},,,{},{},{}",
+                    part,
+                    amount(disposal.shares, units.shares),
+                    SHARE_SYMBOL,
+                    disposal.acquired.map_or_else(|| "VARIOUS".to_string(), date),
+                    date(disposal.sold),
+                    amount(disposal.proceeds, units.assets),
+                    amount(disposal.cost, units.assets),
+                    signed(disposal.proceeds, disposal.cost, units.assets),
+                    disposal.owner,
+                    disposal.tx_hash,
+                )?;
+            }
+        }
+        Format::Generic => {
+            writeln!(
+                out,
+                "Date,Type,Sent Amount,Sent Currency,Received Amount,Received Currency,Cost Basis,Gain,Owner,TxHash"
+            )?;
+            for activity in ledger.activity.iter().filter(|a| selected(a.owner)) {
+                let shares = amount(activity.shares, units.shares);
+                let assets = amount(activity.assets, units.assets);
+                let cost = amount(activity.cost, units.assets);
+                let (kind, sent, sent_currency, received, received_currency, gain) = match activity.kind {
+                    ActivityKind::Deposit => ("buy", assets, ASSET_SYMBOL, shares, SHARE_SYMBOL, String::new()),
+                    ActivityKind::Withdraw => (
+                        "sell",
+                        shares,
+                        SHARE_SYMBOL,
+                        assets,
+                        ASSET_SYMBOL,
+                        signed(activity.assets, activity.cost, units.assets),
+                    ),
+                    ActivityKind::TransferOut => ("transfer_out", shares, SHARE_SYMBOL, String::new(), "", String::new()),
+                    ActivityKind::TransferIn => ("transfer_in", String::new(), "", shares, SHARE_SYMBOL, String::new()),
+                    ActivityKind::TaxWithheld => ("tax_withheld", assets, ASSET_SYMBOL, String::new(), "", String::new()),
+                };
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    date(activity.point),
+                    kind,
+                    sent,
+                    sent_currency,
+                    received,
+                    received_currency,
+                    cost,
+                    gain,
+                    activity.owner,
+                    activity.tx_hash,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn amount(value: U256, decimals: u8) -> String {
+    format_units(value, decimals).unwrap_or_else(|_| value.to_string())
+}
+
+/// `proceeds - cost`, which may be negative
+fn signed(proceeds: U256, cost: U256, decimals: u8) -> String {
+    if proceeds >= cost {
+        amount(proceeds - cost, decimals)
+    } else {
+        format!("-{}", amount(cost - proceeds, decimals))
+    }
+}
+
+/// `MM/DD/YYYY` when the log dump carries block timestamps, the block
+/// number otherwise
+fn date(point: Point) -> String {
+    match point.timestamp {
+        Some(timestamp) => {
+            let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+            format!("{:02}/{:02}/{:04}", month, day, year)
+        }
+        None => format!("block {}", point.block),
+    }
+}
+
+/// Converts days since the Unix epoch to a `(year, month, day)` date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(date(Point { block: 1, timestamp: Some(0) }), "01/01/1970");
+        assert_eq!(date(Point { block: 1, timestamp: Some(1_709_210_096) }), "02/29/2024");
+        assert_eq!(date(Point { block: 7, timestamp: None }), "block 7");
+    }
+
+    #[test]
+    fn prints_losses_as_negative() {
+        assert_eq!(signed(U256::from(1_500_000), U256::from(1_000_000), 6), "0.500000");
+        assert_eq!(signed(U256::from(1_000_000), U256::from(1_500_000), 6), "-0.500000");
+    }
+}
@@ -0,0 +1,376 @@
+//! Rebuilding holder lots and realized gains from vault events
+
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, U256};
+
+use stylus_erc20::{events::TransferMemo, tax::MemoCode};
+
+use crate::logs::{EventRecord, VaultEvent};
+
+/// Which lots a disposal consumes first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// First in, first out (what the vault itself uses)
+    Fifo,
+    /// Last in, first out
+    Lifo,
+    /// Highest cost per share first
+    Hifo,
+}
+
+impl std::str::FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fifo" => Ok(Method::Fifo),
+            "lifo" => Ok(Method::Lifo),
+            "hifo" => Ok(Method::Hifo),
+            other => Err(format!("unknown accounting method `{}`", other)),
+        }
+    }
+}
+
+/// Where an event happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub block: u64,
+    pub timestamp: Option<u64>,
+}
+
+/// Shares acquired together at the same cost
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lot {
+    pub shares: U256,
+    pub cost: U256,
+    /// `None` for shares the log dump holds no acquisition for
+    pub acquired: Option<Point>,
+}
+
+/// A slice of shares redeemed out of a single lot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disposal {
+    pub owner: Address,
+    pub shares: U256,
+    pub proceeds: U256,
+    pub cost: U256,
+    pub acquired: Option<Point>,
+    pub sold: Point,
+    pub tx_hash: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivityKind {
+    Deposit,
+    Withdraw,
+    TransferIn,
+    TransferOut,
+    TaxWithheld,
+}
+
+/// A holder-level movement, as listed by generic tax software
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Activity {
+    pub owner: Address,
+    pub kind: ActivityKind,
+    pub point: Point,
+    pub shares: U256,
+    pub assets: U256,
+    pub cost: U256,
+    pub tx_hash: String,
+}
+
+/// Lots, disposals and activity of every holder
+pub struct Ledger {
+    method: Method,
+    lots: HashMap<Address, Vec<Lot>>,
+    pub disposals: Vec<Disposal>,
+    pub activity: Vec<Activity>,
+    /// Memo logged ahead of the `Transfer` it describes
+    pending_memo: Option<(String, TransferMemo)>,
+}
+
+impl Ledger {
+    pub fn new(method: Method) -> Self {
+        Self { method, lots: HashMap::new(), disposals: Vec::new(), activity: Vec::new(), pending_memo: None }
+    }
+
+    /// Replays `records`, which must be in chain order
+    pub fn replay(method: Method, records: &[EventRecord]) -> Self {
+        let mut ledger = Self::new(method);
+        for record in records {
+            ledger.apply(record);
+        }
+        ledger
+    }
+
+    /// Open lots of `owner`
+    pub fn lots_of(&self, owner: Address) -> &[Lot] {
+        self.lots.get(&owner).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn apply(&mut self, record: &EventRecord) {
+        let point = Point { block: record.block_number, timestamp: record.timestamp };
+        let tx_hash = record.tx_hash.clone();
+
+        match &record.event {
+            VaultEvent::Deposit(deposit) => {
+                self.lots.entry(deposit.owner).or_default().push(Lot {
+                    shares: deposit.shares,
+                    cost: deposit.assets,
+                    acquired: Some(point),
+                });
+                self.activity.push(Activity {
+                    owner: deposit.owner,
+                    kind: ActivityKind::Deposit,
+                    point,
+                    shares: deposit.shares,
+                    assets: deposit.assets,
+                    cost: deposit.assets,
+                    tx_hash,
+                });
+            }
+            VaultEvent::Withdraw(withdraw) => {
+                let taken = self.take(withdraw.owner, withdraw.shares);
+                let cost = self.dispose(withdraw.owner, &taken, withdraw.assets, withdraw.shares, point, &tx_hash);
+                self.activity.push(Activity {
+                    owner: withdraw.owner,
+                    kind: ActivityKind::Withdraw,
+                    point,
+                    shares: withdraw.shares,
+                    assets: withdraw.assets,
+                    cost,
+                    tx_hash,
+                });
+            }
+            VaultEvent::Transfer(transfer) => {
+                // Mints and burns are covered by deposits and withdrawals
+                if transfer.from == Address::ZERO || transfer.to == Address::ZERO {
+                    return;
+                }
+                let memo = self.pending_memo.take().and_then(|(memo_tx, memo)| {
+                    let matches = memo_tx == tx_hash
+                        && memo.from == transfer.from
+                        && memo.to == transfer.to
+                        && memo.value == transfer.value;
+                    matches.then_some(memo)
+                });
+                let taken = self.take(transfer.from, transfer.value);
+                let cost = taken.iter().map(|lot| lot.cost).sum::<U256>();
+
+                match memo.as_ref().and_then(|memo| Some((memo, MemoCode::from_u8(memo.memo_code)?))) {
+                    Some((memo, code)) if code.resets_basis() => {
+                        // Sales realize a gain at the NAV value, inheritances don't
+                        if code == MemoCode::Sale {
+                            self.dispose(transfer.from, &taken, memo.basis_carried, transfer.value, point, &tx_hash);
+                        }
+                        self.lots.entry(transfer.to).or_default().push(Lot {
+                            shares: transfer.value,
+                            cost: memo.basis_carried,
+                            acquired: Some(point),
+                        });
+                    }
+                    _ => self.lots.entry(transfer.to).or_default().extend(taken),
+                }
+                for (owner, kind) in [
+                    (transfer.from, ActivityKind::TransferOut),
+                    (transfer.to, ActivityKind::TransferIn),
+                ] {
+                    self.activity.push(Activity {
+                        owner,
+                        kind,
+                        point,
+                        shares: transfer.value,
+                        assets: U256::ZERO,
+                        cost,
+                        tx_hash: tx_hash.clone(),
+                    });
+                }
+            }
+            VaultEvent::TaxWithheld(withheld) => {
+                self.activity.push(Activity {
+                    owner: withheld.owner,
+                    kind: ActivityKind::TaxWithheld,
+                    point,
+                    shares: U256::ZERO,
+                    assets: withheld.withheld,
+                    cost: U256::ZERO,
+                    tx_hash,
+                });
+            }
+            VaultEvent::TransferMemo(memo) => self.pending_memo = Some((tx_hash, memo.clone())),
+            VaultEvent::Rebalance => {}
+        }
+    }
+
+    /// Records the sale of the `taken` lots of `owner`, totalling `shares`,
+    /// for `proceeds`. Returns the cost basis disposed of.
+    fn dispose(&mut self, owner: Address, taken: &[Lot], proceeds: U256, shares: U256, sold: Point, tx_hash: &str) -> U256 {
+        let mut remaining = proceeds;
+        let mut cost = U256::ZERO;
+        for (i, lot) in taken.iter().enumerate() {
+            // The last slice takes the rounding remainder
+            let slice = if i + 1 == taken.len() { remaining } else { proceeds * lot.shares / shares };
+            remaining -= slice;
+            cost += lot.cost;
+            self.disposals.push(Disposal {
+                owner,
+                shares: lot.shares,
+                proceeds: slice,
+                cost: lot.cost,
+                acquired: lot.acquired,
+                sold,
+                tx_hash: tx_hash.to_string(),
+            });
+        }
+        cost
+    }
+
+    /// Removes `shares` from the lots of `owner` in the ledger's method
+    /// order. Shares beyond the known lots come back with a zero basis and
+    /// no acquisition point.
+    fn take(&mut self, owner: Address, shares: U256) -> Vec<Lot> {
+        let lots = self.lots.entry(owner).or_default();
+        let mut taken = Vec::new();
+        let mut remaining = shares;
+
+        while remaining > U256::ZERO && !lots.is_empty() {
+            let i = match self.method {
+                Method::Fifo => 0,
+                Method::Lifo => lots.len() - 1,
+                Method::Hifo => highest_cost(lots),
+            };
+            if lots[i].shares <= remaining {
+                let lot = lots.remove(i);
+                remaining -= lot.shares;
+                taken.push(lot);
+            } else {
+                let lot = &mut lots[i];
+                let cost = lot.cost * remaining / lot.shares;
+                lot.shares -= remaining;
+                lot.cost -= cost;
+                taken.push(Lot { shares: remaining, cost, acquired: lot.acquired });
+                remaining = U256::ZERO;
+            }
+        }
+
+        if remaining > U256::ZERO {
+            taken.push(Lot { shares: remaining, cost: U256::ZERO, acquired: None });
+        }
+        taken
+    }
+}
+
+/// Index of the lot with the highest cost per share
+fn highest_cost(lots: &[Lot]) -> usize {
+    let mut best = 0;
+    for (i, lot) in lots.iter().enumerate().skip(1) {
+        let top = &lots[best];
+        if lot.cost * top.shares > top.cost * lot.shares {
+            best = i;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use stylus_erc20::events::{Deposit, Transfer, Withdraw};
+
+    const ALICE: Address = address!("bebbe2bacc1f5caf9a471838b7567ff636093c84");
+    const BOB: Address = address!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d");
+
+    fn record(block: u64, event: VaultEvent) -> EventRecord {
+        EventRecord { block_number: block, timestamp: Some(block * 100), tx_hash: String::new(), event }
+    }
+
+    fn deposit(block: u64, owner: Address, assets: u64, shares: u64) -> EventRecord {
+        let event = Deposit { owner, assets: U256::from(assets), shares: U256::from(shares) };
+        record(block, VaultEvent::Deposit(event))
+    }
+
+    fn withdraw(block: u64, owner: Address, shares: u64, assets: u64) -> EventRecord {
+        let event = Withdraw { owner, shares: U256::from(shares), assets: U256::from(assets) };
+        record(block, VaultEvent::Withdraw(event))
+    }
+
+    fn costs(ledger: &Ledger) -> Vec<u64> {
+        ledger.disposals.iter().map(|d| d.cost.to::<u64>()).collect()
+    }
+
+    #[test]
+    fn methods_pick_different_lots() {
+        let records = [
+            deposit(1, ALICE, 100, 100),
+            deposit(2, ALICE, 300, 100),
+            deposit(3, ALICE, 200, 100),
+            withdraw(4, ALICE, 150, 330),
+        ];
+
+        assert_eq!(costs(&Ledger::replay(Method::Fifo, &records)), vec![100, 150]);
+        assert_eq!(costs(&Ledger::replay(Method::Lifo, &records)), vec![200, 150]);
+        assert_eq!(costs(&Ledger::replay(Method::Hifo, &records)), vec![300, 100]);
+    }
+
+    #[test]
+    fn withdraw_proceeds_add_up() {
+        let ledger = Ledger::replay(
+            Method::Fifo,
+            &[deposit(1, ALICE, 100, 30), deposit(2, ALICE, 100, 30), withdraw(3, ALICE, 60, 100)],
+        );
+        let proceeds = ledger.disposals.iter().map(|d| d.proceeds).sum::<U256>();
+        assert_eq!(proceeds, U256::from(100));
+    }
+
+    #[test]
+    fn transfers_carry_lots_to_the_recipient() {
+        let transfer = Transfer { from: ALICE, to: BOB, value: U256::from(40) };
+        let ledger = Ledger::replay(
+            Method::Fifo,
+            &[deposit(1, ALICE, 200, 100), record(2, VaultEvent::Transfer(transfer))],
+        );
+
+        assert_eq!(ledger.lots_of(ALICE)[0].shares, U256::from(60));
+        assert_eq!(ledger.lots_of(BOB)[0].cost, U256::from(80));
+        assert_eq!(ledger.lots_of(BOB)[0].acquired, Some(Point { block: 1, timestamp: Some(100) }));
+    }
+
+    #[test]
+    fn memos_decide_how_basis_moves() {
+        let memo = |code: u8| {
+            let event = TransferMemo {
+                from: ALICE,
+                to: BOB,
+                value: U256::from(100),
+                memo_code: code,
+                reference: Default::default(),
+                basis_released: U256::from(100),
+                basis_carried: U256::from(250),
+            };
+            record(2, VaultEvent::TransferMemo(event))
+        };
+        let transfer = record(2, VaultEvent::Transfer(Transfer { from: ALICE, to: BOB, value: U256::from(100) }));
+
+        let gift = Ledger::replay(Method::Fifo, &[deposit(1, ALICE, 100, 100), memo(1), transfer.clone()]);
+        assert!(gift.disposals.is_empty());
+        assert_eq!(gift.lots_of(BOB)[0].cost, U256::from(100));
+
+        let sale = Ledger::replay(Method::Fifo, &[deposit(1, ALICE, 100, 100), memo(2), transfer.clone()]);
+        assert_eq!(sale.disposals[0].proceeds, U256::from(250));
+        assert_eq!(sale.lots_of(BOB)[0].cost, U256::from(250));
+
+        let inheritance = Ledger::replay(Method::Fifo, &[deposit(1, ALICE, 100, 100), memo(4), transfer]);
+        assert!(inheritance.disposals.is_empty());
+        assert_eq!(inheritance.lots_of(BOB)[0].cost, U256::from(250));
+    }
+
+    #[test]
+    fn untracked_shares_have_no_basis() {
+        let ledger = Ledger::replay(Method::Fifo, &[withdraw(1, ALICE, 10, 50)]);
+        assert_eq!(ledger.disposals[0].cost, U256::ZERO);
+        assert_eq!(ledger.disposals[0].acquired, None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Reading vault logs from an `eth_getLogs` dump
+//!
+//! Accepts a JSON array of logs, a JSON-RPC response whose `result` is that
+//! array, or newline-delimited JSON with one log (or array of logs) per line.
+
+use alloy_primitives::{hex, Address, B256};
+use alloy_sol_types::SolEvent;
+use serde_json::Value;
+use stylus_erc20::events::{Deposit, Rebalance, TaxWithheld, Transfer, TransferMemo, Withdraw};
+
+/// A log as returned by the RPC, before decoding
+#[derive(Clone, Debug)]
+pub struct RawLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Vec<u8>,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub timestamp: Option<u64>,
+    pub tx_hash: String,
+}
+
+/// A decoded vault event
+#[derive(Clone)]
+pub enum VaultEvent {
+    Transfer(Transfer),
+    Deposit(Deposit),
+    Withdraw(Withdraw),
+    /// A trade of the vault's own basket; nothing is realized by holders
+    Rebalance,
+    TaxWithheld(TaxWithheld),
+    TransferMemo(TransferMemo),
+}
+
+/// A decoded vault event with its position in the chain
+#[derive(Clone)]
+pub struct EventRecord {
+    pub block_number: u64,
+    pub timestamp: Option<u64>,
+    pub tx_hash: String,
+    pub event: VaultEvent,
+}
+
+/// Parses every log in `input`, sorted by block and log index.
+/// Logs flagged as `removed` by the node are dropped.
+pub fn parse_logs(input: &str) -> Result<Vec<RawLog>, String> {
+    let mut values = Vec::new();
+    match serde_json::from_str::<Value>(input) {
+        Ok(value) => collect(value, &mut values),
+        Err(_) => {
+            for (n, line) in input.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let value = serde_json::from_str::<Value>(line)
+                    .map_err(|e| format!("line {}: {}", n + 1, e))?;
+                collect(value, &mut values);
+            }
+        }
+    }
+
+    let mut logs = Vec::new();
+    for value in values {
+        if value.get("removed").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        logs.push(parse_log(&value)?);
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
+}
+
+/// Decodes the logs emitted by `vault`. When no vault is given, the emitter
+/// of the first `Deposit` or `Withdraw` is assumed to be the vault, which
+/// keeps unrelated `Transfer` logs (e.g. of USDC) out of the report.
+pub fn decode_logs(logs: &[RawLog], vault: Option<Address>) -> Vec<EventRecord> {
+    let vault = vault.or_else(|| {
+        logs.iter()
+            .find(|log| matches!(log.topics.first(), Some(t) if *t == Deposit::SIGNATURE_HASH || *t == Withdraw::SIGNATURE_HASH))
+            .map(|log| log.address)
+    });
+
+    logs.iter()
+        .filter(|log| vault.map_or(true, |vault| log.address == vault))
+        .filter_map(|log| {
+            decode_event(log).map(|event| EventRecord {
+                block_number: log.block_number,
+                timestamp: log.timestamp,
+                tx_hash: log.tx_hash.clone(),
+                event,
+            })
+        })
+        .collect()
+}
+
+fn decode_event(log: &RawLog) -> Option<VaultEvent> {
+    let topic = *log.topics.first()?;
+    let topics = log.topics.iter().copied();
+    let event = if topic == Transfer::SIGNATURE_HASH {
+        VaultEvent::Transfer(Transfer::decode_raw_log(topics, &log.data, true).ok()?)
+    } else if topic == Deposit::SIGNATURE_HASH {
+        VaultEvent::Deposit(Deposit::decode_raw_log(topics, &log.data, true).ok()?)
+    } else if topic == Withdraw::SIGNATURE_HASH {
+        VaultEvent::Withdraw(Withdraw::decode_raw_log(topics, &log.data, true).ok()?)
+    } else if topic == Rebalance::SIGNATURE_HASH {
+        Rebalance::decode_raw_log(topics, &log.data, true).ok()?;
+        VaultEvent::Rebalance
+    } else if topic == TaxWithheld::SIGNATURE_HASH {
+        VaultEvent::TaxWithheld(TaxWithheld::decode_raw_log(topics, &log.data, true).ok()?)
+    } else if topic == TransferMemo::SIGNATURE_HASH {
+        VaultEvent::TransferMemo(TransferMemo::decode_raw_log(topics, &log.data, true).ok()?)
+    } else {
+        return None;
+    };
+    Some(event)
+}
+
+fn collect(value: Value, out: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => items.into_iter().for_each(|item| collect(item, out)),
+        Value::Object(mut map) if map.contains_key("result") => {
+            collect(map.remove("result").unwrap_or(Value::Null), out)
+        }
+        Value::Object(_) => out.push(value),
+        _ => {}
+    }
+}
+
+fn parse_log(value: &Value) -> Result<RawLog, String> {
+    let field = |name: &str| value.get(name).ok_or_else(|| format!("log without `{}`", name));
+
+    let address = field("address")?
+        .as_str()
+        .and_then(|s| s.parse::<Address>().ok())
+        .ok_or("invalid log address")?;
+    let topics = field("topics")?
+        .as_array()
+        .ok_or("invalid log topics")?
+        .iter()
+        .map(|t| t.as_str().and_then(|s| s.parse::<B256>().ok()).ok_or("invalid log topic"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = field("data")?
+        .as_str()
+        .and_then(|s| hex::decode(s).ok())
+        .ok_or("invalid log data")?;
+
+    Ok(RawLog {
+        address,
+        topics,
+        data,
+        block_number: parse_quantity(field("blockNumber")?).ok_or("invalid block number")?,
+        log_index: value.get("logIndex").and_then(parse_quantity).unwrap_or_default(),
+        timestamp: value.get("blockTimestamp").and_then(parse_quantity),
+        tx_hash: value
+            .get("transactionHash")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// Reads an RPC quantity, either a `0x` hex string or a plain number
+fn parse_quantity(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, U256};
+
+    fn deposit_log(block: u64) -> String {
+        let owner = address!("bebbe2bacc1f5caf9a471838b7567ff636093c84");
+        let event = Deposit { owner, assets: U256::from(1_000), shares: U256::from(1_000) };
+        let topics = event.encode_topics().iter().map(|t| format!("\"{}\"", B256::from(t.0))).collect::<Vec<_>>();
+        format!(
+            r#"{{"address":"0x344f40ca5ccd13642af7eea8abe7566d1ae5ca4f","topics":[{}],"data":"0x{}","blockNumber":"{:#x}","logIndex":"0x0","transactionHash":"0xaa"}}"#,
+            topics.join(","),
+            hex::encode(event.encode_data()),
+            block,
+        )
+    }
+
+    #[test]
+    fn reads_arrays_rpc_responses_and_ndjson() {
+        let array = format!("[{},{}]", deposit_log(2), deposit_log(1));
+        let rpc = format!(r#"{{"jsonrpc":"2.0","id":1,"result":{}}}"#, array);
+        let ndjson = format!("{}\n\n{}\n", deposit_log(2), deposit_log(1));
+
+        for input in [array, rpc, ndjson] {
+            let logs = parse_logs(&input).unwrap();
+            assert_eq!(logs.iter().map(|l| l.block_number).collect::<Vec<_>>(), vec![1, 2]);
+            assert_eq!(decode_logs(&logs, None).len(), 2);
+        }
+    }
+
+    #[test]
+    fn ignores_logs_from_other_contracts() {
+        let logs = parse_logs(&format!("[{}]", deposit_log(1))).unwrap();
+        let other = address!("75faf114eafb1bdbe2f0316df893fd58ce46aa4d");
+        assert!(decode_logs(&logs, Some(other)).is_empty());
+    }
+}
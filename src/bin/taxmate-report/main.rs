@@ -0,0 +1,94 @@
+//! `taxmate-report`: offline tax report exporter for the vault
+//!
+//! Reads a JSON or NDJSON dump of the vault's logs (as returned by
+//! `eth_getLogs`), rebuilds every holder's lots and realized gains, and
+//! writes them as CSV.
+//!
+//! ```text
+//! taxmate-report <LOGS> [--format form8949|generic] [--method fifo|lifo|hifo]
+//!                       [--owner <ADDRESS>] [--vault <ADDRESS>] [--out <FILE>]
+//!                       [--asset-decimals <N>] [--share-decimals <N>]
+//! ```
+
+mod export;
+mod ledger;
+mod logs;
+
+use std::{fs, io, process};
+
+use alloy_primitives::Address;
+
+use crate::export::{write_csv, Format, Units};
+use crate::ledger::{Ledger, Method};
+
+const USAGE: &str = "usage: taxmate-report <LOGS> [--format form8949|generic] [--method fifo|lifo|hifo] \
+[--owner <ADDRESS>] [--vault <ADDRESS>] [--out <FILE>] [--asset-decimals <N>] [--share-decimals <N>]";
+
+/// Parsed command line
+struct Options {
+    input: String,
+    format: Format,
+    method: Method,
+    owner: Option<Address>,
+    vault: Option<Address>,
+    out: Option<String>,
+    units: Units,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        input: String::new(),
+        format: Format::Form8949,
+        method: Method::Fifo,
+        owner: None,
+        vault: None,
+        out: None,
+        units: Units { assets: 6, shares: 18 },
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--format" => options.format = value()?.parse()?,
+            "--method" => options.method = value()?.parse()?,
+            "--owner" => options.owner = Some(value()?.parse().map_err(|_| "invalid --owner address")?),
+            "--vault" => options.vault = Some(value()?.parse().map_err(|_| "invalid --vault address")?),
+            "--out" => options.out = Some(value()?),
+            "--asset-decimals" => options.units.assets = value()?.parse().map_err(|_| "invalid --asset-decimals")?,
+            "--share-decimals" => options.units.shares = value()?.parse().map_err(|_| "invalid --share-decimals")?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ if options.input.is_empty() => options.input = arg,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    if options.input.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let input = fs::read_to_string(&options.input).map_err(|e| format!("{}: {}", options.input, e))?;
+    let raw = logs::parse_logs(&input)?;
+    let records = logs::decode_logs(&raw, options.vault);
+    let ledger = Ledger::replay(options.method, &records);
+
+    match &options.out {
+        Some(path) => {
+            let mut file = fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            write_csv(&mut file, &ledger, options.format, options.owner, options.units)
+        }
+        None => write_csv(&mut io::stdout().lock(), &ledger, options.format, options.owner, options.units),
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn main() {
+    let result = parse_args(std::env::args().skip(1)).and_then(run);
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
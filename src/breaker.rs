@@ -0,0 +1,110 @@
+//! Drawdown circuit breaker
+//!
+//! The vault tracks the highest NAV per share it has seen. Once NAV per
+//! share falls more than `max_drawdown_bps` below that peak the breaker
+//! trips: rebalances stop until the guardian resumes them, while deposits
+//! and withdrawals carry on.
+//!
+//! Deposits mint shares 1:1 for USDC, so they move NAV per share without
+//! anyone gaining or losing. The peak is carried across them, keeping the
+//! drawdown where it was, and an empty vault has no NAV per share at all.
+
+// Imported packages
+use alloy_primitives::U256;
+use crate::tax::BPS_DENOMINATOR;
+
+/// Scale of NAV per share, so it keeps precision for 18-decimal shares
+pub const SHARE_PRICE_SCALE: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+/// NAV per share, scaled by [`SHARE_PRICE_SCALE`]; `None` without shares
+pub fn share_price(nav: U256, supply: U256) -> Option<U256> {
+    if supply.is_zero() {
+        return None;
+    }
+    Some(nav * SHARE_PRICE_SCALE / supply)
+}
+
+/// Peak after a deposit moved NAV per share from `before` to `after`:
+/// scaled by the same factor, so the drawdown is unchanged. A vault that
+/// was empty, or worthless, starts over from `after`.
+pub fn carry_peak(peak: U256, before: Option<U256>, after: Option<U256>) -> U256 {
+    match (before, after) {
+        (Some(before), Some(after)) if !before.is_zero() => peak * after / before,
+        (_, Some(after)) => after,
+        (_, None) => peak,
+    }
+}
+
+/// How far `price` is below `peak`, in bps
+pub fn drawdown_bps(price: U256, peak: U256) -> u16 {
+    if peak.is_zero() || price >= peak {
+        return 0;
+    }
+    ((peak - price) * U256::from(BPS_DENOMINATOR) / peak).to::<u16>()
+}
+
+/// Whether `price` is more than `max_drawdown_bps` below `peak`; a zero
+/// threshold never trips
+pub fn breached(price: U256, peak: U256, max_drawdown_bps: u16) -> bool {
+    max_drawdown_bps != 0 && drawdown_bps(price, peak) > max_drawdown_bps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_shares() {
+        assert_eq!(share_price(U256::from(2_000), U256::from(1_000)), Some(U256::from(2) * SHARE_PRICE_SCALE));
+        assert_eq!(share_price(U256::ZERO, U256::from(1_000)), Some(U256::ZERO));
+    }
+
+    #[test]
+    fn has_no_price_without_shares() {
+        // An empty vault is not a 100% drawdown
+        assert_eq!(share_price(U256::from(2_000), U256::ZERO), None);
+        assert_eq!(share_price(U256::ZERO, U256::ZERO), None);
+    }
+
+    #[test]
+    fn deposits_do_not_read_as_drawdowns() {
+        // NAV per share doubled to 2, then a large 1:1 deposit drags it down
+        let peak = share_price(U256::from(2_000), U256::from(1_000)).unwrap();
+        let before = share_price(U256::from(2_000), U256::from(1_000));
+        let after = share_price(U256::from(10_000), U256::from(9_000));
+        assert!(breached(after.unwrap(), peak, 1_000));
+
+        let carried = carry_peak(peak, before, after);
+        assert_eq!(drawdown_bps(after.unwrap(), carried), 0);
+
+        // Real losses after the deposit still trip the breaker
+        let loss = share_price(U256::from(8_900), U256::from(9_000)).unwrap();
+        assert!(breached(loss, carried, 1_000));
+    }
+
+    #[test]
+    fn restarts_the_peak_of_an_empty_vault() {
+        let after = share_price(U256::from(1_000), U256::from(1_000));
+        assert_eq!(carry_peak(U256::from(7), None, after), SHARE_PRICE_SCALE);
+        assert_eq!(carry_peak(U256::from(7), Some(U256::ZERO), after), SHARE_PRICE_SCALE);
+        assert_eq!(carry_peak(U256::from(7), Some(U256::from(1)), None), U256::from(7));
+        assert_eq!(carry_peak(U256::ZERO, Some(U256::from(2)), Some(U256::from(1))), U256::ZERO);
+    }
+
+    #[test]
+    fn measures_drawdowns() {
+        let peak = U256::from(10_000);
+        assert_eq!(drawdown_bps(U256::from(9_000), peak), 1_000);
+        assert_eq!(drawdown_bps(U256::from(11_000), peak), 0);
+        assert_eq!(drawdown_bps(U256::ZERO, U256::ZERO), 0);
+        assert_eq!(drawdown_bps(U256::ZERO, peak), BPS_DENOMINATOR);
+    }
+
+    #[test]
+    fn trips_past_the_threshold() {
+        let peak = U256::from(10_000);
+        assert!(!breached(U256::from(9_000), peak, 1_000));
+        assert!(breached(U256::from(8_999), peak, 1_000));
+        assert!(!breached(U256::ZERO, peak, 0));
+    }
+}
@@ -0,0 +1,23 @@
+//! Typed calls to external contracts
+//!
+//! Calls are encoded with the `sol!` definitions of the `vault-abi` crate
+//! and their return data decoded with the same definitions, so the contract
+//! and off-chain tools share a single description of every interface.
+
+// Imported packages
+use alloy_primitives::Address;
+use alloy_sol_types::SolCall;
+use stylus_sdk::call::{self, MutatingCallContext, StaticCallContext};
+
+/// Sends `call` to `to` and decodes what it returns. Reverts and
+/// undecodable return data are handed back raw.
+pub fn call_contract<C: SolCall>(context: impl MutatingCallContext, to: Address, call: &C) -> Result<C::Return, Vec<u8>> {
+    let return_data = call::call(context, to, &call.abi_encode())?;
+    C::abi_decode_returns(&return_data, true).map_err(|_| return_data)
+}
+
+/// Sends the view `call` to `to` and decodes what it returns
+pub fn static_call_contract<C: SolCall>(context: impl StaticCallContext, to: Address, call: &C) -> Result<C::Return, Vec<u8>> {
+    let return_data = call::static_call(context, to, &call.abi_encode())?;
+    C::abi_decode_returns(&return_data, true).map_err(|_| return_data)
+}
@@ -0,0 +1,32 @@
+//! Commit-reveal rebalances
+//!
+//! Rebalance calldata is public before it executes. To keep trades private
+//! the keeper first commits the hash of an order, and reveals the order
+//! itself only `delay` blocks later, within `ttl` blocks, when the vault
+//! checks it against the commitment and runs it. This is opt-in: the
+//! keeper's other rebalance paths stay immediate, and it commits only the
+//! trades worth hiding.
+
+/// Blocks to wait before revealing while no window is configured
+pub const DEFAULT_REVEAL_DELAY: u64 = 1;
+
+/// Blocks a commitment may be revealed in while no window is configured
+pub const DEFAULT_REVEAL_TTL: u64 = 300;
+
+/// First and last block a commitment made at `committed_at` may be
+/// revealed in
+pub fn reveal_window(committed_at: u64, delay: u64, ttl: u64) -> (u64, u64) {
+    let opens_at = committed_at.saturating_add(delay);
+    (opens_at, opens_at.saturating_add(ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_the_delay_for_the_ttl() {
+        assert_eq!(reveal_window(100, 2, 50), (102, 152));
+        assert_eq!(reveal_window(u64::MAX - 1, 2, 50), (u64::MAX, u64::MAX));
+    }
+}
@@ -0,0 +1,103 @@
+//! Rebalance epochs
+//!
+//! Time is cut into epochs of `epoch_length` seconds. The first trade of
+//! an epoch opens its record with the NAV it started from; every trade
+//! after that adds its turnover and gas and moves the ending NAV, so
+//! performance reports and the turnover limit share one time base. Epochs
+//! without trades leave no record.
+
+// Imported packages
+use alloy_primitives::{U256, U32, U64};
+use stylus_sdk::prelude::*;
+
+/// Epoch length while none is configured (one day)
+pub const DEFAULT_EPOCH_LENGTH: u64 = 86_400;
+
+sol_storage! {
+    /// What happened during one epoch.
+    pub struct EpochRecord {
+        uint64 started_at;
+        /// NAV before the epoch's first trade
+        uint256 start_nav;
+        /// NAV after the epoch's last trade
+        uint256 end_nav;
+        /// USDC value traded
+        uint256 turnover;
+        uint256 gas_used;
+        uint32 rebalances;
+    }
+}
+
+/// An epoch record read out of storage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochState {
+    pub started_at: u64,
+    pub start_nav: U256,
+    pub end_nav: U256,
+    pub turnover: U256,
+    pub gas_used: U256,
+    pub rebalances: u32,
+}
+
+impl EpochRecord {
+    /// Reads the record out of storage
+    pub fn state(&self) -> EpochState {
+        EpochState {
+            started_at: self.started_at.get().to::<u64>(),
+            start_nav: self.start_nav.get(),
+            end_nav: self.end_nav.get(),
+            turnover: self.turnover.get(),
+            gas_used: self.gas_used.get(),
+            rebalances: self.rebalances.get().to::<u32>(),
+        }
+    }
+
+    /// Opens the record of an epoch starting at `now` from `nav`
+    pub fn open(&mut self, now: u64, nav: U256) {
+        self.started_at.set(U64::from(now));
+        self.start_nav.set(nav);
+        self.end_nav.set(nav);
+    }
+
+    /// Adds a trade that left NAV at `nav`
+    pub fn record(&mut self, nav: U256, turnover: U256, gas_used: u64) {
+        self.end_nav.set(nav);
+        self.turnover.set(self.turnover.get() + turnover);
+        self.gas_used.set(self.gas_used.get() + U256::from(gas_used));
+        self.rebalances.set(self.rebalances.get() + U32::from(1));
+    }
+}
+
+/// Whether the epoch that started at `started_at` is over at `now`. No
+/// epoch has started while `started_at` is zero.
+pub fn epoch_over(started_at: u64, epoch_length: u64, now: u64) -> bool {
+    started_at == 0 || now >= started_at.saturating_add(epoch_length)
+}
+
+/// When a rebalance may next run after the last one at `last_rebalance_at`
+pub fn next_rebalance_at(last_rebalance_at: u64, min_interval: u64) -> u64 {
+    if last_rebalance_at == 0 {
+        return 0;
+    }
+    last_rebalance_at.saturating_add(min_interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_epochs_over() {
+        assert!(epoch_over(0, DEFAULT_EPOCH_LENGTH, 5));
+        assert!(!epoch_over(1_000, DEFAULT_EPOCH_LENGTH, 1_000 + DEFAULT_EPOCH_LENGTH - 1));
+        assert!(epoch_over(1_000, DEFAULT_EPOCH_LENGTH, 1_000 + DEFAULT_EPOCH_LENGTH));
+        assert!(!epoch_over(1_000, u64::MAX, u64::MAX - 1));
+    }
+
+    #[test]
+    fn spaces_rebalances_out() {
+        assert_eq!(next_rebalance_at(0, 3_600), 0);
+        assert_eq!(next_rebalance_at(1_000, 3_600), 4_600);
+        assert_eq!(next_rebalance_at(u64::MAX, 3_600), u64::MAX);
+    }
+}
@@ -0,0 +1,36 @@
+//! Events emitted by the vault
+//!
+//! These are public so that off-chain tools built from this crate, such as
+//! the `taxmate-report` exporter, decode logs with the exact definitions the
+//! contract emits.
+
+// Imported packages
+use alloy_sol_types::sol;
+
+pub use crate::erc20::Transfer;
+
+sol! {
+    event Deposit(address indexed owner, uint256 assets, uint256 shares);
+    event Withdraw(address indexed owner, uint256 shares, uint256 assets);
+    event Rebalance(address indexed token_in, address indexed token_out, uint256 amount_in, uint256 amount_out);
+    event TaxWithheld(address indexed owner, address indexed treasury, uint256 proceeds, uint256 gain, uint256 withheld);
+    event WithholdingRateSet(address indexed owner, uint16 rate);
+    event RebalancedToWeights(address[] tokens, uint16[] target_weights_bps, uint16[] weights_bps);
+    event RiskLimitsSet(uint16 min_cash_bps, uint16 max_turnover_bps, uint16 max_nav_drop_bps);
+    event MaxWeightSet(address indexed token, uint16 max_weight_bps);
+    event CircuitBreakerTripped(uint256 nav, uint256 peak);
+    event CircuitBreakerReset(address indexed guardian, uint256 nav);
+    event SlicedOrderSubmitted(uint256 indexed order_id, address token_in, address token_out, uint256 amount_in, uint16 slices, uint64 interval);
+    event SliceExecuted(uint256 indexed order_id, uint16 slice, uint256 amount_in, uint256 amount_out);
+    event SlicedOrderCancelled(uint256 indexed order_id);
+    event TriggerRegistered(uint256 indexed trigger_id, address indexed token, uint16 sell_bps, uint256 stop_price, uint256 take_price);
+    event TriggerExecuted(uint256 indexed trigger_id, address indexed executor, uint256 price, uint256 amount_in, uint256 amount_out, uint256 bounty);
+    event TriggerCancelled(uint256 indexed trigger_id);
+    event EpochStarted(uint256 indexed epoch_id, uint256 start_nav);
+    event RebalanceCommitted(bytes32 indexed commitment, uint64 block_number);
+    event RebalanceRevealed(bytes32 indexed commitment);
+    event LegFailed(uint256 indexed leg, address indexed token_in, address indexed token_out, uint256 amount_in, string reason);
+    event SignedRebalance(uint256 indexed nonce, address indexed relayer);
+    event SwapFailed(address indexed token_in, address indexed token_out, uint256 amount_in, string reason);
+    event TransferMemo(address indexed from, address indexed to, uint256 value, uint8 memo_code, bytes32 reference, uint256 basis_released, uint256 basis_carried);
+}
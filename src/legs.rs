@@ -0,0 +1,67 @@
+//! Rebalance legs
+//!
+//! A rebalance is a batch of legs, each a single swap. In atomic mode the
+//! first failed leg reverts the whole batch with `LegReverted`; in
+//! best-effort mode failed legs are logged as `LegFailed` and skipped, and
+//! the batch reports which legs went through as a bitmap, bit `i` standing
+//! for leg `i`.
+
+// Imported packages
+use alloy_primitives::{Address, U256};
+
+/// Most legs a batch may have, one per bit of the bitmap
+pub const MAX_LEGS: usize = 256;
+
+/// A single swap of a rebalance
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Leg {
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    /// Least output the caller accepts, on top of the oracle's bound
+    pub min_amount_out: U256,
+}
+
+/// What a rebalance does when one of its legs fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebalanceMode {
+    /// Log the failure and carry on with the next leg
+    BestEffort = 0,
+    /// Revert the whole batch with the router's error
+    Atomic = 1,
+}
+
+impl RebalanceMode {
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(Self::BestEffort),
+            1 => Some(Self::Atomic),
+            _ => None,
+        }
+    }
+}
+
+/// `bitmap` with leg `leg` marked as succeeded
+pub fn mark_succeeded(bitmap: U256, leg: usize) -> U256 {
+    bitmap | (U256::from(1) << leg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(RebalanceMode::from_u8(0), Some(RebalanceMode::BestEffort));
+        assert_eq!(RebalanceMode::from_u8(1), Some(RebalanceMode::Atomic));
+        assert_eq!(RebalanceMode::from_u8(2), None);
+    }
+
+    #[test]
+    fn marks_legs_in_the_bitmap() {
+        let bitmap = mark_succeeded(mark_succeeded(U256::ZERO, 0), 255);
+        assert!(bitmap.bit(0) && bitmap.bit(MAX_LEGS - 1));
+        assert!(!bitmap.bit(1));
+        assert_eq!(mark_succeeded(U256::ZERO, 2), U256::from(4));
+    }
+}
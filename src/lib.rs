@@ -3,16 +3,62 @@
 extern crate alloc;
 
 // Modules and imports
+mod basket;
+mod breaker;
+mod calls;
+mod commit_reveal;
 mod erc20;
+mod epochs;
+pub mod events;
+mod legs;
+mod nav;
+mod risk;
+mod slippage;
+pub mod tax;
+mod triggers;
+mod twap;
+mod weights;
 
+use crate::basket::{Basket, TokenSettings, DEFAULT_FEE};
+use crate::breaker::{breached, carry_peak, share_price};
+use crate::calls::{call_contract, static_call_contract};
+use crate::commit_reveal::{reveal_window, DEFAULT_REVEAL_DELAY, DEFAULT_REVEAL_TTL};
+use crate::epochs::{epoch_over, next_rebalance_at, EpochRecord, DEFAULT_EPOCH_LENGTH};
 use crate::erc20::{Erc20, Erc20Error, Erc20Params};
-use alloy_primitives::{Address, U256};
+use crate::events::{CircuitBreakerReset, CircuitBreakerTripped, Deposit, EpochStarted, Rebalance, LegFailed, MaxWeightSet, RebalanceCommitted, RebalanceRevealed, RebalancedToWeights, RiskLimitsSet, SignedRebalance, SliceExecuted, SlicedOrderCancelled, SlicedOrderSubmitted, TriggerCancelled, TriggerExecuted, TriggerRegistered, SwapFailed, TaxWithheld, TransferMemo, Withdraw, WithholdingRateSet};
+use crate::legs::{mark_succeeded, Leg, RebalanceMode, MAX_LEGS};
+use crate::nav::{share_value, shares_for_value, token_value};
+use crate::risk::{check_cash, check_nav_drop, check_turnover, check_weight, Breach, RiskLimits};
+use crate::slippage::{
+    expected_out, max_amount_in, min_amount_out, sqrt_price_limit_x96, Quote, DEFAULT_MAX_SLIPPAGE_BPS, MAX_SLIPPAGE_BPS,
+};
+use crate::tax::{
+    harvestable_losses, pro_rata_cost, realized_gain, withholding_amount, AccountingMode, CostBasis, Lot, MemoCode,
+    BPS_DENOMINATOR, MAX_WITHHOLDING_BPS,
+};
+use crate::triggers::{bounty, PriceTrigger, TriggerState, MAX_TRIGGER_BOUNTY_BPS};
+use crate::twap::SlicedOrder;
+use crate::weights::{fit_buys, plan_rebalance, valid_weights, weight_bps, Holding, DEFAULT_WEIGHT_TOLERANCE_BPS};
+use alloy_primitives::{Address, FixedBytes, U16, U256, U64, U8};
+use vault_abi::dex::Venue;
+use vault_abi::erc20::IERC20;
+use vault_abi::oracle::IPriceOracle;
+use vault_abi::receipts::IReceipts;
+use vault_abi::order::{commitment, decode_ecrecover, ecrecover_input, signing_hash, CommittedRebalance, RebalanceOrder, ECRECOVER};
+use vault_abi::revert::decode_revert;
+use vault_abi::router::{
+    decode_path, encode_path, join_paths, reverse_path, ExactInput, ExactInputSingle, ExactOutput,
+    ExactOutputSingle, RouterKind,
+};
 use stylus_sdk::{
-    call::{Call, call},
+    abi::Bytes,
+    block,
+    call::{Call, call, static_call},
     msg, 
     contract,
     evm,
     prelude::*,
+    storage::StorageGuard,
 };
 use alloy_sol_types::sol;
 
@@ -31,122 +77,852 @@ sol_storage! {
         
         Erc20<VaultTokenParams> erc20;
         address metric_address;
-        address[] enabled_tokens;
+        Basket basket;
         address usdc_address;
         address router_address;
+        address admin;
+        address withholding_treasury;
+        mapping(address => uint16) withholding_rates;
+        CostBasis cost_basis;
+        address oracle_address;
+        uint8 accounting_mode;
+        address receipts_address;
+        mapping(uint256 => Lot) receipt_lots;
+        uint8 router_kind;
+        mapping(uint8 => address) venue_routers;
+        mapping(address => mapping(address => uint8)) pair_venues;
+        mapping(address => bytes) paths_to_usdc;
+        mapping(address => bytes) paths_from_usdc;
+        uint8 rebalance_mode;
+        uint16 max_slippage_bps;
+        uint16 weight_tolerance_bps;
+        uint256 order_nonce;
+        mapping(address => mapping(address => bytes)) pair_paths;
+        uint16 min_cash_bps;
+        uint16 max_turnover_bps;
+        uint16 max_nav_drop_bps;
+        uint64 last_rebalance_at;
+        uint256 epoch_id;
+        address guardian;
+        uint16 max_drawdown_bps;
+        uint256 peak_share_price;
+        bool breaker_tripped;
+        SlicedOrder[] sliced_orders;
+        PriceTrigger[] triggers;
+        uint16 trigger_bounty_bps;
+        mapping(uint256 => EpochRecord) epochs;
+        uint64 epoch_length;
+        uint64 min_rebalance_interval;
+        mapping(bytes32 => uint64) commitments;
+        uint64 reveal_delay_blocks;
+        uint64 reveal_ttl_blocks;
     }
 }
 
-// Define external ERC20 interface for calling other contracts
-sol_interface! {
-    interface IERC20 {
-        function balanceOf(address account) external view returns (uint256);
-        function transfer(address recipient, uint256 amount) external returns (bool);
-        function transferFrom(address sender, address recipient, uint256 amou2nt) external returns (bool);
-        function approve(address spender, uint256 amount) external returns (bool);
-        function allowance(address owner, address spender) external view returns (uint256);
+// Declare events and Solidity error types
+sol! {
+    error NotAuthorized();
+    error InvalidWithholdingRate(uint16 rate);
+    error TransferFailed(address token);
+    error OracleFailed(address token);
+    error BalanceQueryFailed(address token);
+    error InvalidMemoCode(uint8 memo_code);
+    error WrongAccountingMode(uint8 mode);
+    error AccountingModeLocked();
+    error InsufficientReceiptShares(uint256 receipt_id, uint256 shares);
+    error ReceiptsFailed(address receipts);
+    error InvalidRouterKind(uint8 kind);
+    error InvalidVenue(uint8 venue);
+    error InvalidPath(address token);
+    error InvalidTokenConfig(address token);
+    error InvalidSlippage(uint16 bps);
+    error InvalidWeights();
+    error InvalidTolerance(uint16 bps);
+    error InvalidSignature();
+    error OrderExpired(uint256 deadline);
+    error InvalidNonce(uint256 nonce);
+    error LengthMismatch(uint256[] lengths);
+    error TokenNotEnabled(address token);
+    error ZeroAmount(uint256 leg);
+    error InsufficientTokenBalance(address token, uint256 balance, uint256 amount);
+    error TooManyLegs(uint256 legs);
+    error InvalidRebalanceMode(uint8 mode);
+    error InvalidRiskLimits();
+    error MaxWeightExceeded(address token, uint16 weight_bps, uint16 max_weight_bps);
+    error CashBufferBreached(uint16 cash_bps, uint16 min_cash_bps);
+    error TurnoverExceeded(uint256 turnover, uint256 max_turnover);
+    error NavDropExceeded(uint16 drop_bps, uint16 max_nav_drop_bps);
+    error InvalidDrawdown(uint16 bps);
+    error RebalancingPaused();
+    error DrawdownExceeded(uint256 nav, uint256 peak);
+    error InvalidSlicedOrder();
+    error UnknownOrder(uint256 order_id);
+    error OrderClosed(uint256 order_id);
+    error SliceNotDue(uint256 order_id, uint64 due_at);
+    error SliceFailed(uint256 order_id);
+    error InvalidTrigger(address token);
+    error UnknownTrigger(uint256 trigger_id);
+    error TriggerInactive(uint256 trigger_id);
+    error TriggerNotMet(uint256 trigger_id, uint256 price);
+    error TriggerFailed(uint256 trigger_id);
+    error InvalidBounty(uint16 bps);
+    error RebalanceCooldown(uint64 next_at);
+    error InvalidEpochLength(uint64 seconds);
+    error InvalidRevealWindow();
+    error CommitmentExists(bytes32 commitment);
+    error CommitmentMismatch(bytes32 commitment);
+    error RevealTooEarly(uint64 opens_at);
+    error CommitmentExpired(uint64 expired_at);
+    error ApproveFailed(address token);
+    error AssetsUnavailable(uint256 assets, uint256 shortfall);
+    error MaxSharesExceeded(uint256 shares, uint256 max_shares);
+    error NoLegs();
+    error LegReverted(uint256 leg, address token_in, address token_out, string reason);
+}
+
+/// Represents the ways vault methods may fail.
+#[derive(SolidityError)]
+pub enum VaultError {
+    NotAuthorized(NotAuthorized),
+    InvalidWithholdingRate(InvalidWithholdingRate),
+    TransferFailed(TransferFailed),
+    OracleFailed(OracleFailed),
+    BalanceQueryFailed(BalanceQueryFailed),
+    InvalidMemoCode(InvalidMemoCode),
+    WrongAccountingMode(WrongAccountingMode),
+    AccountingModeLocked(AccountingModeLocked),
+    InsufficientReceiptShares(InsufficientReceiptShares),
+    ReceiptsFailed(ReceiptsFailed),
+    InvalidRouterKind(InvalidRouterKind),
+    InvalidVenue(InvalidVenue),
+    InvalidPath(InvalidPath),
+    InvalidTokenConfig(InvalidTokenConfig),
+    InvalidSlippage(InvalidSlippage),
+    InvalidWeights(InvalidWeights),
+    InvalidTolerance(InvalidTolerance),
+    InvalidSignature(InvalidSignature),
+    OrderExpired(OrderExpired),
+    InvalidNonce(InvalidNonce),
+    LengthMismatch(LengthMismatch),
+    TokenNotEnabled(TokenNotEnabled),
+    ZeroAmount(ZeroAmount),
+    InsufficientTokenBalance(InsufficientTokenBalance),
+    TooManyLegs(TooManyLegs),
+    InvalidRebalanceMode(InvalidRebalanceMode),
+    InvalidRiskLimits(InvalidRiskLimits),
+    MaxWeightExceeded(MaxWeightExceeded),
+    CashBufferBreached(CashBufferBreached),
+    TurnoverExceeded(TurnoverExceeded),
+    NavDropExceeded(NavDropExceeded),
+    InvalidDrawdown(InvalidDrawdown),
+    RebalancingPaused(RebalancingPaused),
+    DrawdownExceeded(DrawdownExceeded),
+    InvalidSlicedOrder(InvalidSlicedOrder),
+    UnknownOrder(UnknownOrder),
+    OrderClosed(OrderClosed),
+    SliceNotDue(SliceNotDue),
+    SliceFailed(SliceFailed),
+    InvalidTrigger(InvalidTrigger),
+    UnknownTrigger(UnknownTrigger),
+    TriggerInactive(TriggerInactive),
+    TriggerNotMet(TriggerNotMet),
+    TriggerFailed(TriggerFailed),
+    InvalidBounty(InvalidBounty),
+    RebalanceCooldown(RebalanceCooldown),
+    InvalidEpochLength(InvalidEpochLength),
+    InvalidRevealWindow(InvalidRevealWindow),
+    CommitmentExists(CommitmentExists),
+    CommitmentMismatch(CommitmentMismatch),
+    RevealTooEarly(RevealTooEarly),
+    CommitmentExpired(CommitmentExpired),
+    ApproveFailed(ApproveFailed),
+    AssetsUnavailable(AssetsUnavailable),
+    MaxSharesExceeded(MaxSharesExceeded),
+    NoLegs(NoLegs),
+    LegReverted(LegReverted),
+}
+
+/// `(lot_index, shares, cost, value, shares_needed, acquired_at)`
+pub type HarvestableLossRow = (U256, U256, U256, U256, U256, u64);
+
+/// `(token, fee, decimals, oracle, max_weight_bps, enabled)`
+pub type TokenConfigRow = (Address, u32, u8, Address, u16, bool);
+
+/// `(amount_in, amount_out)` of a rebalance leg
+pub type LegResult = (U256, U256);
+
+/// Results of every leg of a rebalance and the bitmap of legs that succeeded
+pub type RebalanceOutcome = (Vec<LegResult>, U256);
+
+/// `(started_at, start_nav, end_nav, turnover, gas_used, rebalances)`
+pub type EpochInfoRow = (u64, U256, U256, U256, U256, u32);
+
+/// `(token, sell_bps, stop_price, take_price, active)`
+pub type TriggerRow = (Address, u16, U256, U256, bool);
+
+/// `(token_in, token_out, amount_in, amount_out, slices, slices_done,
+/// interval, last_executed, cancelled)`
+pub type SlicedOrderRow = (Address, Address, U256, U256, u16, u16, u64, u64, bool);
+
+impl From<Erc20Error> for VaultError {
+    fn from(err: Erc20Error) -> Self {
+        match err {}
     }
 }
 
 #[public]
 #[inherit(Erc20<VaultTokenParams>)]
 impl Vault {
-    pub fn deposit(&mut self, amount_in: U256) -> Result<(), Erc20Error> {
+    pub fn deposit(&mut self, amount_in: U256) -> Result<(), VaultError> {
+        // NAV per share before the deposit, to carry the drawdown peak over
+        let price_before = self._tracked_share_price();
+
         // Get the USDC address first
         let usdc_address = self.usdc_address.get();
-        let usdc = IERC20::new(usdc_address);
+        let transfer_from = IERC20::transferFromCall { sender: msg::sender(), recipient: contract::address(), amount: amount_in };
         let config = Call::new_in(self)   
             .gas(evm::gas_left() / 2);    
         // Call the transferFrom function on the USDC contract
-        usdc.transfer_from(config, msg::sender(), contract::address(), amount_in);
+        match call_contract(config, usdc_address, &transfer_from) {
+            Ok(ret) if ret._0 => {}
+            _ => return Err(VaultError::TransferFailed(TransferFailed { token: usdc_address })),
+        }
         // Mint the vault tokens to the sender
 
         // {Calculate the amount of vault tokens to mint}
         self.erc20.mint(msg::sender(), amount_in)?;
 
+        // Open a tax lot for the freshly minted shares, or mint a receipt for it
+        match self._accounting_mode() {
+            AccountingMode::Lots => self.cost_basis.add_lot(msg::sender(), amount_in, amount_in),
+            AccountingMode::Receipts => {
+                let receipt_id = self._mint_receipt(msg::sender())?;
+                let mut lot = self.receipt_lots.setter(receipt_id);
+                lot.shares.set(amount_in);
+                lot.cost.set(amount_in);
+                lot.acquired_at.set(U64::from(block::timestamp()));
+            }
+        }
+
+        // Shares are minted 1:1, which moves NAV per share without a loss
+        if let (Some(before), Some(after)) = (price_before, self._tracked_share_price()) {
+            let peak = self.peak_share_price.get();
+            self.peak_share_price.set(carry_peak(peak, before, after));
+        }
+
+        evm::log(Deposit { owner: msg::sender(), assets: amount_in, shares: amount_in });
+
         Ok(())
     }
     
-    pub fn withdraw(&mut self, amount_out: U256) -> Result<(), Erc20Error> {
-        // Calculate the % of the supply that the user has with scaling to maintain precision
+    pub fn withdraw(&mut self, amount_out: U256) -> Result<(), VaultError> {
+        self._require_mode(AccountingMode::Lots)?;
+        let proceeds = self._redeem(msg::sender(), amount_out)?;
+
+        // Release the basis of the redeemed shares, oldest lots first
+        let basis = self.cost_basis.consume(msg::sender(), amount_out);
+        self._settle_withdrawal(msg::sender(), amount_out, proceeds, basis)
+    }
+
+    /// Withdraws exactly `assets` USDC, burning at most `max_shares` of the
+    /// caller's shares. Their share of the vault's USDC is paid first, the
+    /// rest is bought with exact output swaps of their share of each basket
+    /// token, at most `max_slippage_bps` off the oracle. Shares are burned
+    /// for the oracle value of what was spent, so what the swaps did not
+    /// need stays in the vault for the caller. Returns the shares burned.
+    pub fn withdraw_assets(&mut self, assets: U256, max_shares: U256) -> Result<U256, Vec<u8>> {
+        self._require_mode(AccountingMode::Lots)?;
+        let owner = msg::sender();
+        if assets.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        // Nothing may come out of more than the shares the caller offers
+        let shares_cap = max_shares.min(self.erc20.balance_of(owner));
+        if shares_cap.is_zero() {
+            return Err(VaultError::AssetsUnavailable(AssetsUnavailable { assets, shortfall: assets }).into());
+        }
         let supply = self.erc20.total_supply();
-        // Use a scaling factor of 10^18 to handle decimal percentages
-        let scaling_factor = U256::from(10).pow(U256::from(18));
-        let percentage = (amount_out * scaling_factor) / supply;
-        
+        let nav = self._total_assets()?;
         let usdc_address = self.usdc_address.get();
-        let router_address = self.router_address.get();
-        
-        // Get the enabled_tokens length and iterate manually
-        let mut i = 0;
-        loop {
-            // Try to get the token at the current index
-            let token_opt = self.enabled_tokens.get(i);
-            
-            // If we get None, we've reached the end of the array
-            if token_opt.is_none() {
+        let slippage = self._max_slippage_bps();
+
+        // Cash first, swaps only for what it does not cover
+        let cash = self._balance_of(usdc_address)? * shares_cap / supply;
+        let mut remaining = assets - assets.min(cash);
+        let mut spent_value = assets - remaining;
+
+        for token in self.basket.tokens() {
+            if remaining.is_zero() {
                 break;
             }
-            
-            let token = token_opt.unwrap();
-            
-            // Get the balance of this token that the contract owns
-            let token_contract = IERC20::new(token);
-            let config = Call::new_in(self).gas(evm::gas_left() / 2);
-            
-            // Try to get the token balance, handle errors properly
-            let token_balance = match token_contract.balance_of(config, contract::address()) {
-                Ok(balance) => balance,
-                Err(_) => {
-                    // Skip this token if balance check fails, continue to next token
-                    i += 1;
-                    continue;
-                }
-            };
-            
-            // Calculate the amount to transfer based on user's percentage
-            // Divide by scaling factor to get actual amount
-            let share_total = (token_balance * percentage) / scaling_factor;
-            
-            if share_total > U256::ZERO {
-                if token != usdc_address {
-                    // For non-USDC tokens, approve and swap to USDC
-                    let approve_config = Call::new_in(self).gas(evm::gas_left() / 2);
-                    let _ = token_contract.approve(approve_config, router_address, share_total);
-                    
-                    // Perform the swap - ignore errors and continue
-                    let _ = self._swap_tokens(token, usdc_address, 3000, share_total, U256::ZERO);
-                } else {
-                    // For USDC, transfer directly to user
-                    let transfer_config = Call::new_in(self).gas(evm::gas_left() / 2);
-                    let _ = token_contract.transfer(transfer_config, msg::sender(), share_total);
-                }
+            if token == usdc_address {
+                continue;
             }
-            
-            i += 1;
+            let budget = self._balance_of(token)? * shares_cap / supply;
+            let quote = self._quote(token)?;
+            if budget.is_zero() || quote.price.is_zero() {
+                continue;
+            }
+
+            // Buy what the budget fetches at the worst price allowed, and
+            // spend no more than the oracle cost plus slippage on it
+            let amount_out = remaining.min(min_amount_out(expected_out(budget, quote, Quote::USDC), slippage));
+            if amount_out.is_zero() {
+                continue;
+            }
+            let amount_in_maximum = max_amount_in(expected_out(amount_out, Quote::USDC, quote), slippage).min(budget);
+            let spent = self._swap_to_exact_usdc(token, amount_out, amount_in_maximum)?;
+            remaining -= amount_out;
+            spent_value += token_value(spent, quote.price, quote.decimals);
         }
-        
-        // Burn the user's tokens after all swaps are done
-        self.erc20.burn(msg::sender(), amount_out)?;
-        
-        Ok(())
+        if !remaining.is_zero() {
+            return Err(VaultError::AssetsUnavailable(AssetsUnavailable { assets, shortfall: remaining }).into());
+        }
+
+        let shares = shares_for_value(spent_value, nav, supply);
+        if shares > shares_cap {
+            return Err(VaultError::MaxSharesExceeded(MaxSharesExceeded { shares, max_shares }).into());
+        }
+        self.erc20.burn(owner, shares)?;
+
+        // Release the basis of the burned shares, oldest lots first
+        let basis = self.cost_basis.consume(owner, shares);
+        self._settle_withdrawal(owner, shares, assets, basis)?;
+        Ok(shares)
     }
 
-    pub fn initialize(&mut self, metric_address: Address, usdc_address: Address, router_address: Address, enabled_tokens: Vec<Address>) {
+    pub fn initialize(&mut self, metric_address: Address, usdc_address: Address, router_address: Address, enabled_tokens: Vec<Address>) -> Result<(), VaultError> {
+        // The first caller becomes the admin, later calls must come from it
+        let admin = self.admin.get();
+        if admin == Address::ZERO {
+            self.admin.set(msg::sender());
+        } else if admin != msg::sender() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
+        }
+
         self.metric_address.set(metric_address);
         self.usdc_address.set(usdc_address);
         self.router_address.set(router_address);
         
-        // Clear any existing tokens and add new ones
-        let length = self.enabled_tokens.len();
-        for _ in 0..length {
-            self.enabled_tokens.pop();
-        }
+        // Disable any existing tokens and enable the new ones
+        self.basket.disable_all();
         
-        // Add each token from the input vector
+        // Add each token from the input vector, with default settings if new
         for token in enabled_tokens {
-            self.enabled_tokens.push(token);
+            if self.basket.set_enabled(token, true) {
+                continue;
+            }
+            let decimals = static_call_contract(&*self, token, &IERC20::decimalsCall {})
+                .map_err(|_| VaultError::InvalidTokenConfig(InvalidTokenConfig { token }))?
+                ._0;
+            self.basket.set(TokenSettings::new(token, decimals));
+        }
+
+        Ok(())
+    }
+
+    /// Redeems `shares` out of receipt `receipt_id`, which the caller must
+    /// own. The receipt is burned once all its shares are withdrawn.
+    pub fn withdraw_from_receipt(&mut self, receipt_id: U256, shares: U256) -> Result<(), VaultError> {
+        self._require_mode(AccountingMode::Receipts)?;
+        let owner = self._receipt_owner(receipt_id)?;
+        if owner != msg::sender() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
+        }
+
+        let lot = self.receipt_lots.get(receipt_id);
+        let lot_shares = lot.shares.get();
+        let lot_cost = lot.cost.get();
+        if shares.is_zero() || shares > lot_shares {
+            return Err(VaultError::InsufficientReceiptShares(InsufficientReceiptShares { receipt_id, shares }));
+        }
+
+        let proceeds = self._redeem(owner, shares)?;
+
+        // Reduce the receipt by the redeemed shares, burning it once empty
+        let basis = pro_rata_cost(lot_shares, lot_cost, shares);
+        let mut lot = self.receipt_lots.setter(receipt_id);
+        lot.shares.set(lot_shares - shares);
+        lot.cost.set(lot_cost - basis);
+        if shares == lot_shares {
+            self._burn_receipt(receipt_id)?;
+        }
+
+        self._settle_withdrawal(owner, shares, proceeds, basis)
+    }
+
+    /// Owner, shares, cost and acquisition time recorded by receipt `receipt_id`
+    pub fn receipt(&self, receipt_id: U256) -> Result<(Address, U256, U256, u64), VaultError> {
+        let owner = self._receipt_owner(receipt_id)?;
+        let lot = self.receipt_lots.get(receipt_id);
+        Ok((owner, lot.shares.get(), lot.cost.get(), lot.acquired_at.get().to::<u64>()))
+    }
+
+    /// Moves the shares receipt `receipt_id` records from `from` to `to`;
+    /// called by the receipts contract on every receipt transfer
+    pub fn on_receipt_transfer(&mut self, from: Address, to: Address, receipt_id: U256) -> Result<(), VaultError> {
+        if msg::sender() != self.receipts_address.get() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
+        }
+        let shares = self.receipt_lots.get(receipt_id).shares.get();
+        self.erc20._transfer(from, to, shares)?;
+        Ok(())
+    }
+
+    /// Sets the ERC-721 contract deposit receipts are minted on and binds it
+    /// to this vault (admin only); reverts if it was already initialized
+    pub fn set_receipts_address(&mut self, receipts: Address) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if !receipts.has_code() {
+            return Err(VaultError::ReceiptsFailed(ReceiptsFailed { receipts }));
+        }
+        call_contract(Call::new_in(self), receipts, &IReceipts::initializeCall {})
+            .map_err(|_| VaultError::ReceiptsFailed(ReceiptsFailed { receipts }))?;
+        self.receipts_address.set(receipts);
+        Ok(())
+    }
+
+    /// ERC-721 contract deposit receipts are minted on
+    pub fn receipts_address(&self) -> Address {
+        self.receipts_address.get()
+    }
+
+    /// Switches between pooled lots (0) and deposit receipts (1); only
+    /// possible while no shares are outstanding, and receipts need their
+    /// contract set first
+    pub fn set_accounting_mode(&mut self, mode: u8) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let mode = AccountingMode::from_u8(mode)
+            .ok_or(VaultError::WrongAccountingMode(WrongAccountingMode { mode }))?;
+        if self.erc20.total_supply() > U256::ZERO {
+            return Err(VaultError::AccountingModeLocked(AccountingModeLocked {}));
+        }
+        if mode == AccountingMode::Receipts && self.receipts_address.get() == Address::ZERO {
+            return Err(VaultError::ReceiptsFailed(ReceiptsFailed { receipts: Address::ZERO }));
+        }
+        self.accounting_mode.set(U8::from(mode as u8));
+        Ok(())
+    }
+
+    /// Current accounting mode: pooled lots (0) or deposit receipts (1)
+    pub fn accounting_mode(&self) -> u8 {
+        self._accounting_mode() as u8
+    }
+
+    /// Transfers shares to `to`, carrying their cost basis along
+    pub fn transfer(&mut self, to: Address, value: U256) -> Result<bool, VaultError> {
+        self._require_mode(AccountingMode::Lots)?;
+        self.erc20.transfer(to, value)?;
+        self.cost_basis.carry_over(msg::sender(), to, value);
+        Ok(true)
+    }
+
+    /// Transfers shares from `from` to `to`, carrying their cost basis along
+    pub fn transfer_from(&mut self, from: Address, to: Address, value: U256) -> Result<bool, VaultError> {
+        self._require_mode(AccountingMode::Lots)?;
+        self.erc20.transfer_from(from, to, value)?;
+        self.cost_basis.carry_over(from, to, value);
+        Ok(true)
+    }
+
+    /// Transfers shares to `to`, tagging the transfer with a memo code
+    /// (1 gift, 2 sale, 3 internal move, 4 inheritance) and an off-chain
+    /// `reference`. Sales and inheritances reset the recipient's basis to
+    /// the shares' NAV value, gifts and internal moves carry it over.
+    pub fn transfer_with_memo(&mut self, to: Address, value: U256, memo_code: u8, reference: FixedBytes<32>) -> Result<bool, VaultError> {
+        self._require_mode(AccountingMode::Lots)?;
+        let memo = MemoCode::from_u8(memo_code)
+            .ok_or(VaultError::InvalidMemoCode(InvalidMemoCode { memo_code }))?;
+        let from = msg::sender();
+
+        let (basis_released, basis_carried) = if memo.resets_basis() {
+            let fair_value = share_value(value, self._total_assets()?, self.erc20.total_supply());
+            let released = self.cost_basis.consume(from, value);
+            self.cost_basis.add_lot(to, value, fair_value);
+            (released, fair_value)
+        } else {
+            let carried = self.cost_basis.carry_over(from, to, value);
+            (carried, carried)
+        };
+
+        // Logged ahead of the `Transfer` it describes
+        evm::log(TransferMemo { from, to, value, memo_code, reference, basis_released, basis_carried });
+        self.erc20.transfer(to, value)?;
+        Ok(true)
+    }
+
+    /// Total cost basis of the shares held by `owner`
+    pub fn cost_basis_of(&self, owner: Address) -> U256 {
+        self.cost_basis.basis_of(owner)
+    }
+
+    /// Opts the caller into withholding `rate` bps of realized gains (0 opts out)
+    pub fn set_withholding_rate(&mut self, rate: u16) -> Result<(), VaultError> {
+        if rate > MAX_WITHHOLDING_BPS {
+            return Err(VaultError::InvalidWithholdingRate(InvalidWithholdingRate { rate }));
+        }
+        self.withholding_rates.insert(msg::sender(), U16::from(rate));
+        evm::log(WithholdingRateSet { owner: msg::sender(), rate });
+        Ok(())
+    }
+
+    /// Withholding rate `owner` opted into, in bps
+    pub fn withholding_rate(&self, owner: Address) -> u16 {
+        self.withholding_rates.get(owner).to::<u16>()
+    }
+
+    /// Sets the address withheld taxes are sent to
+    pub fn set_withholding_treasury(&mut self, treasury: Address) -> Result<(), VaultError> {
+        self._only_admin()?;
+        self.withholding_treasury.set(treasury);
+        Ok(())
+    }
+
+    /// Address withheld taxes are sent to
+    pub fn withholding_treasury(&self) -> Address {
+        self.withholding_treasury.get()
+    }
+
+    /// Selects the router flavour behind `router_address`:
+    /// `SwapRouter02` (0) or the original `SwapRouter` (1)
+    pub fn set_router_kind(&mut self, kind: u8) -> Result<(), VaultError> {
+        self._only_admin()?;
+        RouterKind::from_u8(kind).ok_or(VaultError::InvalidRouterKind(InvalidRouterKind { kind }))?;
+        self.router_kind.set(U8::from(kind));
+        Ok(())
+    }
+
+    /// Router flavour behind `router_address`
+    pub fn router_kind(&self) -> u8 {
+        self._router_kind() as u8
+    }
+
+    /// Sets the router of `venue`: Uniswap V3 (0), Uniswap V2 (1) or
+    /// Algebra (2). The Uniswap V3 router is `router_address`.
+    pub fn set_venue_router(&mut self, venue: u8, router: Address) -> Result<(), VaultError> {
+        self._only_admin()?;
+        match Venue::from_u8(venue) {
+            Some(Venue::UniswapV3) => self.router_address.set(router),
+            Some(_) => self.venue_routers.insert(U8::from(venue), router),
+            None => return Err(VaultError::InvalidVenue(InvalidVenue { venue })),
+        }
+        Ok(())
+    }
+
+    /// Router of `venue`
+    pub fn venue_router(&self, venue: u8) -> Result<Address, VaultError> {
+        let venue = Venue::from_u8(venue).ok_or(VaultError::InvalidVenue(InvalidVenue { venue }))?;
+        Ok(self._venue_router(venue))
+    }
+
+    /// Routes swaps between `token_a` and `token_b`, in either direction,
+    /// through `venue`
+    pub fn set_pair_venue(&mut self, token_a: Address, token_b: Address, venue: u8) -> Result<(), VaultError> {
+        self._only_admin()?;
+        Venue::from_u8(venue).ok_or(VaultError::InvalidVenue(InvalidVenue { venue }))?;
+        self.pair_venues.setter(token_a).insert(token_b, U8::from(venue));
+        self.pair_venues.setter(token_b).insert(token_a, U8::from(venue));
+        Ok(())
+    }
+
+    /// Venue swaps between `token_a` and `token_b` go through
+    pub fn pair_venue(&self, token_a: Address, token_b: Address) -> u8 {
+        self._pair_venue(token_a, token_b) as u8
+    }
+
+    /// Sets the packed Uniswap V3 paths `token` is swapped along to and from
+    /// USDC, for tokens without a direct USDC pool. Empty paths fall back
+    /// to the direct pool.
+    pub fn set_usdc_paths(&mut self, token: Address, path_to_usdc: Bytes, path_from_usdc: Bytes) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let usdc_address = self.usdc_address.get();
+        let runs_between = |path: &[u8], from: Address, to: Address| {
+            path.is_empty()
+                || decode_path(path).is_some_and(|(tokens, _)| tokens.first() == Some(&from) && tokens.last() == Some(&to))
+        };
+        if !runs_between(&path_to_usdc, token, usdc_address) || !runs_between(&path_from_usdc, usdc_address, token) {
+            return Err(VaultError::InvalidPath(InvalidPath { token }));
+        }
+
+        self.paths_to_usdc.setter(token).set_bytes(&*path_to_usdc);
+        self.paths_from_usdc.setter(token).set_bytes(&*path_from_usdc);
+        Ok(())
+    }
+
+    /// Paths `token` is swapped along to and from USDC, empty for the direct pool
+    pub fn usdc_paths(&self, token: Address) -> (Bytes, Bytes) {
+        (
+            self.paths_to_usdc.getter(token).get_bytes().into(),
+            self.paths_from_usdc.getter(token).get_bytes().into(),
+        )
+    }
+
+    /// Sets the packed Uniswap V3 path token-to-token legs from `token_in`
+    /// to `token_out` are swapped along. Empty paths fall back to joining
+    /// the two tokens' routes through USDC.
+    pub fn set_pair_path(&mut self, token_in: Address, token_out: Address, path: Bytes) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let runs_between = decode_path(&path).is_some_and(|(tokens, _)| tokens.first() == Some(&token_in) && tokens.last() == Some(&token_out));
+        if !path.is_empty() && !runs_between {
+            return Err(VaultError::InvalidPath(InvalidPath { token: token_in }));
+        }
+        self.pair_paths.setter(token_in).setter(token_out).set_bytes(&*path);
+        Ok(())
+    }
+
+    /// Path legs from `token_in` to `token_out` are swapped along, empty if
+    /// they go through USDC
+    pub fn pair_path(&self, token_in: Address, token_out: Address) -> Bytes {
+        self.pair_paths.getter(token_in).getter(token_out).get_bytes().into()
+    }
+
+    /// Adds `token` to the basket or updates its config: the fee tier of its
+    /// USDC pool, its decimals, the oracle pricing it (zero for the vault's
+    /// oracle) and the largest share of the vault it may make up, in bps
+    pub fn set_token_config(&mut self, token: Address, fee: u32, decimals: u8, oracle: Address, max_weight_bps: u16, enabled: bool) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let settings = TokenSettings { token, fee, decimals, oracle, max_weight_bps, enabled };
+        if !settings.is_valid() {
+            return Err(VaultError::InvalidTokenConfig(InvalidTokenConfig { token }));
+        }
+        self.basket.set(settings);
+        evm::log(MaxWeightSet { token, max_weight_bps });
+        Ok(())
+    }
+
+    /// Enables or disables a basket token; disabled tokens are still valued
+    /// and redeemed but no longer bought by rebalances
+    pub fn set_token_enabled(&mut self, token: Address, enabled: bool) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if !self.basket.set_enabled(token, enabled) {
+            return Err(VaultError::InvalidTokenConfig(InvalidTokenConfig { token }));
+        }
+        Ok(())
+    }
+
+    /// Config of every basket token, as
+    /// `(token, fee, decimals, oracle, max_weight_bps, enabled)`
+    pub fn token_configs(&self) -> Vec<TokenConfigRow> {
+        self.basket
+            .configs()
+            .into_iter()
+            .map(|config| (config.token, config.fee, config.decimals, config.oracle, config.max_weight_bps, config.enabled))
+            .collect()
+    }
+
+    /// Sets what rebalances do when a leg fails: log `LegFailed` and carry
+    /// on (0) or revert the whole batch with the router's error (1)
+    pub fn set_rebalance_mode(&mut self, mode: u8) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let mode = RebalanceMode::from_u8(mode)
+            .ok_or(VaultError::InvalidRebalanceMode(InvalidRebalanceMode { mode }))?;
+        self.rebalance_mode.set(U8::from(mode as u8));
+        Ok(())
+    }
+
+    /// Current rebalance mode: best-effort (0) or atomic (1)
+    pub fn rebalance_mode(&self) -> u8 {
+        self._rebalance_mode() as u8
+    }
+
+    /// Sets how far below the oracle quote a rebalance leg may fill, in bps
+    pub fn set_max_slippage(&mut self, bps: u16) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if bps == 0 || bps > MAX_SLIPPAGE_BPS {
+            return Err(VaultError::InvalidSlippage(InvalidSlippage { bps }));
+        }
+        self.max_slippage_bps.set(U16::from(bps));
+        Ok(())
+    }
+
+    /// How far below the oracle quote a rebalance leg may fill, in bps
+    pub fn max_slippage(&self) -> u16 {
+        self._max_slippage_bps()
+    }
+
+    /// Sets how far, in bps of NAV, a token may drift from its target weight
+    /// before `rebalance_to_weights` trades it
+    pub fn set_weight_tolerance(&mut self, bps: u16) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if bps == 0 || bps > BPS_DENOMINATOR {
+            return Err(VaultError::InvalidTolerance(InvalidTolerance { bps }));
+        }
+        self.weight_tolerance_bps.set(U16::from(bps));
+        Ok(())
+    }
+
+    /// How far a token may drift from its target weight, in bps of NAV
+    pub fn weight_tolerance(&self) -> u16 {
+        self._weight_tolerance_bps()
+    }
+
+    /// Sets the risk limits every rebalance is checked against, in bps of
+    /// NAV: the USDC buffer to keep, the value traded per epoch (of the
+    /// epoch's starting NAV) and the NAV a single rebalance may lose. Zero
+    /// disables a limit.
+    pub fn set_risk_limits(&mut self, min_cash_bps: u16, max_turnover_bps: u16, max_nav_drop_bps: u16) -> Result<(), VaultError> {
+        self._only_admin()?;
+        let limits = RiskLimits { min_cash_bps, max_turnover_bps, max_nav_drop_bps };
+        if !limits.is_valid() {
+            return Err(VaultError::InvalidRiskLimits(InvalidRiskLimits {}));
+        }
+        self.min_cash_bps.set(U16::from(min_cash_bps));
+        self.max_turnover_bps.set(U16::from(max_turnover_bps));
+        self.max_nav_drop_bps.set(U16::from(max_nav_drop_bps));
+        evm::log(RiskLimitsSet { min_cash_bps, max_turnover_bps, max_nav_drop_bps });
+        Ok(())
+    }
+
+    /// Risk limits as `(min_cash_bps, max_turnover_bps, max_nav_drop_bps)`
+    pub fn risk_limits(&self) -> (u16, u16, u16) {
+        let limits = self._risk_limits();
+        (limits.min_cash_bps, limits.max_turnover_bps, limits.max_nav_drop_bps)
+    }
+
+    /// Sets the length of an epoch in seconds (admin only)
+    pub fn set_epoch_length(&mut self, seconds: u64) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if seconds == 0 {
+            return Err(VaultError::InvalidEpochLength(InvalidEpochLength { seconds }));
+        }
+        self.epoch_length.set(U64::from(seconds));
+        Ok(())
+    }
+
+    /// Length of an epoch in seconds
+    pub fn epoch_length(&self) -> u64 {
+        self._epoch_length()
+    }
+
+    /// Sets the least number of seconds between two rebalances; zero lets
+    /// them run back to back (admin only)
+    pub fn set_min_rebalance_interval(&mut self, seconds: u64) -> Result<(), VaultError> {
+        self._only_admin()?;
+        self.min_rebalance_interval.set(U64::from(seconds));
+        Ok(())
+    }
+
+    /// Least number of seconds between two rebalances
+    pub fn min_rebalance_interval(&self) -> u64 {
+        self.min_rebalance_interval.get().to::<u64>()
+    }
+
+    /// Id of the latest epoch with trades, zero before the first one
+    pub fn current_epoch(&self) -> U256 {
+        self.epoch_id.get()
+    }
+
+    /// Record of epoch `id`: when it started, its starting and ending NAV,
+    /// the USDC value traded, the gas its trades used and how many ran
+    pub fn epoch_info(&self, id: U256) -> EpochInfoRow {
+        let epoch = self.epochs.getter(id).state();
+        (epoch.started_at, epoch.start_nav, epoch.end_nav, epoch.turnover, epoch.gas_used, epoch.rebalances)
+    }
+
+    /// Sets the guardian, who resumes rebalancing after the circuit breaker
+    /// trips
+    pub fn set_guardian(&mut self, guardian: Address) -> Result<(), VaultError> {
+        self._only_admin()?;
+        self.guardian.set(guardian);
+        Ok(())
+    }
+
+    /// Address allowed to resume rebalancing
+    pub fn guardian(&self) -> Address {
+        self.guardian.get()
+    }
+
+    /// Sets how far, in bps, NAV per share may fall below its peak before
+    /// the circuit breaker pauses rebalancing. Zero disables the breaker.
+    pub fn set_max_drawdown(&mut self, bps: u16) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if bps > BPS_DENOMINATOR {
+            return Err(VaultError::InvalidDrawdown(InvalidDrawdown { bps }));
+        }
+        self.max_drawdown_bps.set(U16::from(bps));
+        Ok(())
+    }
+
+    /// Trips the circuit breaker if NAV per share has fallen too far below
+    /// its peak, otherwise records a new peak. An empty vault has no NAV
+    /// per share to check. Anyone may call it, as it can only ever trip the
+    /// breaker; only the guardian resets it. Returns whether it is tripped.
+    pub fn check_drawdown(&mut self) -> Result<bool, VaultError> {
+        if self.breaker_tripped.get() {
+            return Ok(true);
+        }
+        let Some(price) = self._share_price()? else {
+            return Ok(false);
+        };
+        let peak = self.peak_share_price.get();
+        if breached(price, peak, self.max_drawdown_bps.get().to::<u16>()) {
+            self.breaker_tripped.set(true);
+            evm::log(CircuitBreakerTripped { nav: price, peak });
+            return Ok(true);
+        }
+        if price > peak {
+            self.peak_share_price.set(price);
+        }
+        Ok(false)
+    }
+
+    /// Resumes rebalancing after the breaker tripped, measuring drawdowns
+    /// from the current NAV per share from now on, or from the first one
+    /// once an empty vault has shares again (guardian only)
+    pub fn resume_rebalancing(&mut self) -> Result<(), VaultError> {
+        if msg::sender() != self.guardian.get() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
         }
+        let price = self._share_price()?.unwrap_or_default();
+        self.breaker_tripped.set(false);
+        self.peak_share_price.set(price);
+        evm::log(CircuitBreakerReset { guardian: msg::sender(), nav: price });
+        Ok(())
+    }
+
+    /// Breaker state as `(tripped, nav_per_share, peak, max_drawdown_bps)`,
+    /// NAV per share scaled by 1e18 and zero while the vault is empty
+    pub fn circuit_breaker(&self) -> Result<(bool, U256, U256, u16), VaultError> {
+        Ok((
+            self.breaker_tripped.get(),
+            self._share_price()?.unwrap_or_default(),
+            self.peak_share_price.get(),
+            self.max_drawdown_bps.get().to::<u16>(),
+        ))
+    }
+
+    /// Sets the price oracle used to value the vault
+    pub fn set_oracle(&mut self, oracle: Address) -> Result<(), VaultError> {
+        self._only_admin()?;
+        self.oracle_address.set(oracle);
+        Ok(())
+    }
+
+    /// USDC value of everything the vault holds
+    pub fn total_assets(&self) -> Result<U256, VaultError> {
+        self._total_assets()
+    }
+
+    /// Lots of `owner` currently at an unrealized loss, as
+    /// `(lot_index, shares, cost, value, shares_needed, acquired_at)`.
+    /// `shares_needed` counts the older lots a redemption consumes first.
+    pub fn harvestable_losses(&self, owner: Address) -> Result<Vec<HarvestableLossRow>, VaultError> {
+        let nav = self._total_assets()?;
+        let supply = self.erc20.total_supply();
+        let lots = self.cost_basis.open_lots(owner);
+
+        Ok(harvestable_losses(&lots, nav, supply)
+            .into_iter()
+            .map(|loss| (
+                U256::from(loss.lot.index),
+                loss.lot.shares,
+                loss.lot.cost,
+                loss.value,
+                loss.shares_needed,
+                loss.lot.acquired_at,
+            ))
+            .collect())
     }
 
     /*  
@@ -169,21 +945,15 @@ impl Vault {
     pub fn vault_balances(&mut self) -> Result<Vec<U256>, Vec<u8>> {
         let mut balances = Vec::new();
         
-        // Get the length of enabled_tokens
-        let length = self.enabled_tokens.len();
-        
-        // Iterate through indices manually
-        for i in 0..length {
-            // Safe unwrap since we're checking within bounds
-            if let Some(token) = self.enabled_tokens.get(i) {
-                let token_contract = IERC20::new(token);
-                let config = Call::new_in(self).gas(evm::gas_left() / 2);
-                
-                // Handle the Result returned by balance_of
-                match token_contract.balance_of(config, contract::address()) {
-                    Ok(balance) => balances.push(balance),
-                    Err(_) => balances.push(U256::ZERO)  // Push zero if there's an error
-                }
+        // Iterate through every basket token
+        for token in self.basket.tokens() {
+            let balance_of = IERC20::balanceOfCall { account: contract::address() };
+            let config = Call::new_in(self).gas(evm::gas_left() / 2);
+            
+            // Handle the Result returned by balanceOf
+            match static_call_contract(config, token, &balance_of) {
+                Ok(ret) => balances.push(ret._0),
+                Err(_) => balances.push(U256::ZERO)  // Push zero if there's an error
             }
         }
         Ok(balances)
@@ -224,66 +994,897 @@ impl Vault {
         Ok(result)
     }
  */
-    pub fn rebalance(&mut self, tokens_to_swap: Vec<Address>, zero_to_one: Vec<bool>, amount_in: Vec<U256>) {
-          /* if msg::sender() != self.metric_address.get() {
-            return Err(Erc20Error::NotAuthorized);
-        }   
-         */
+    /// Brings each of `tokens` to `weights_bps` of the vault's NAV, leaving
+    /// USDC as the rest. Tokens within the tolerance band are not traded;
+    /// overweight tokens are sold before underweight ones are bought with
+    /// the USDC on hand. Returns the weights reached.
+    pub fn rebalance_to_weights(&mut self, tokens: Vec<Address>, weights_bps: Vec<u16>) -> Result<Vec<u16>, Vec<u8>> {
+        self._only_keeper()?;
+        self._guard_drawdown()?;
+        self._check_cooldown()?;
+        let gas_start = evm::gas_left();
         let usdc_address = self.usdc_address.get();
-        let router_address = self.router_address.get();
-        let max_len = tokens_to_swap.len().min(zero_to_one.len());
-        
-        for i in 0..max_len {
-            let token = tokens_to_swap[i];
-            let is_zero_to_one = zero_to_one[i];
-            
-            if is_zero_to_one {
-                // Swapping token -> USDC
-                let token_contract = IERC20::new(token);
-                
-                // Create a new config for each call
-                let config = Call::new_in(self).gas(evm::gas_left());
-                let token_balance = match token_contract.balance_of(config, contract::address()) {
-                    Ok(balance) => balance,
-                    Err(_) => continue,
-                };
-                
-                if token_balance > U256::ZERO {
-                    // Create a new config for the approve call
-                    let config = Call::new_in(self).gas(evm::gas_left());
-                    let _ = token_contract.approve(config, router_address, amount_in[i]);
-                    
-                    // Swap token -> USDC
-                    let _ = self._swap_tokens(token, usdc_address, 3000, amount_in[i], U256::ZERO);
-                }
-            } else {
-                // Swapping USDC -> token
-                // Get USDC contract
-                let usdc_contract = IERC20::new(usdc_address);
-                
-                // Create a new config for balance check
-                let config = Call::new_in(self).gas(evm::gas_left());
-                let usdc_balance = match usdc_contract.balance_of(config, contract::address()) {
-                    Ok(balance) => balance,
-                    Err(_) => continue,
-                };
-                
-                if usdc_balance > U256::ZERO {
-                    // Create a new config for approve
-                    let config = Call::new_in(self).gas(evm::gas_left());
-                    let _ = usdc_contract.approve(config, router_address, amount_in[i]);
-                    
-              
-                    let _ = self._swap_tokens(usdc_address, token, 3000, amount_in[i], U256::ZERO);
-                }
+        if tokens.len() != weights_bps.len() || !valid_weights(&weights_bps) {
+            return Err(VaultError::InvalidWeights(InvalidWeights {}).into());
+        }
+        for (index, (token, weight)) in tokens.iter().zip(&weights_bps).enumerate() {
+            // A token listed twice would be planned, and traded, twice
+            if tokens[..index].contains(token) {
+                return Err(VaultError::InvalidWeights(InvalidWeights {}).into());
+            }
+            let config = self.basket.get(*token).filter(|config| config.enabled && *token != usdc_address);
+            if !config.is_some_and(|config| *weight <= config.max_weight_bps) {
+                return Err(VaultError::InvalidWeights(InvalidWeights {}).into());
             }
         }
+
+        // Value the basket and plan the trades
+        let nav = self._total_assets()?;
+        let cash = self._balance_of(usdc_address)?;
+        let holdings = self._holdings(&tokens)?;
+        let plan = plan_rebalance(&holdings, &weights_bps, nav, self._weight_tolerance_bps());
+
+        // Sell first, so the buys can spend the proceeds
+        let mut leg = 0;
+        let mut swapped = false;
+        let mut traded = U256::ZERO;
+        let mut bought = Vec::new();
+        for (index, amount) in plan.sells {
+            let token = tokens[index];
+            let (amount_out_minimum, sqrt_price_limit_x96) = self._swap_bounds(token, usdc_address, amount)?;
+            match self._swap_to_usdc(token, amount, amount_out_minimum, sqrt_price_limit_x96) {
+                Ok(amount_out) => {
+                    evm::log(Rebalance { token_in: token, token_out: usdc_address, amount_in: amount, amount_out });
+                    swapped = true;
+                    traded += expected_out(amount, self._quote(token)?, Quote::USDC);
+                }
+                Err(data) => self._rebalance_swap_failed(leg, token, usdc_address, amount, data)?,
+            }
+            leg += 1;
+        }
+
+        let mut buys = plan.buys;
+        fit_buys(&mut buys, self._balance_of(usdc_address)?);
+        for (index, amount) in buys {
+            let token = tokens[index];
+            if amount.is_zero() {
+                continue;
+            }
+            let (amount_out_minimum, sqrt_price_limit_x96) = self._swap_bounds(usdc_address, token, amount)?;
+            match self._swap_from_usdc(token, amount, amount_out_minimum, sqrt_price_limit_x96) {
+                Ok(amount_out) => {
+                    evm::log(Rebalance { token_in: usdc_address, token_out: token, amount_in: amount, amount_out });
+                    swapped = true;
+                    traded += amount;
+                    bought.push(token);
+                }
+                Err(data) => self._rebalance_swap_failed(leg, usdc_address, token, amount, data)?,
+            }
+            leg += 1;
+        }
+        if swapped {
+            self._check_risk(nav, cash, &bought, traded, gas_start)?;
+            self._start_cooldown();
+        }
+
+        // Report the weights reached
+        let nav = self._total_assets()?;
+        let weights: Vec<u16> = self._holdings(&tokens)?.iter().map(|holding| weight_bps(holding.value, nav)).collect();
+        evm::log(RebalancedToWeights { tokens, target_weights_bps: weights_bps, weights_bps: weights.clone() });
+        Ok(weights)
+    }
+
+    /// Swaps each of `tokens_to_swap` to USDC (`zero_to_one`) or USDC to
+    /// it, spending `amount_in`. Returns `(amount_in, amount_out)` of every
+    /// leg, with zero out for legs whose swap failed, and the bitmap of legs
+    /// that went through.
+    pub fn rebalance(&mut self, tokens_to_swap: Vec<Address>, zero_to_one: Vec<bool>, amount_in: Vec<U256>) -> Result<RebalanceOutcome, Vec<u8>> {
+        // Anyone else has to go through a signed order
+        self._only_keeper()?;
+        self._rebalance(tokens_to_swap, zero_to_one, amount_in)
+    }
+
+    /// Swaps `amount_in` of each of `tokens_in` directly to the matching
+    /// token of `tokens_out`, receiving at least `min_amount_out` and no
+    /// less than the oracle allows. Either side may be USDC. Returns the
+    /// same per-leg results and bitmap as `rebalance`.
+    pub fn rebalance_pairs(
+        &mut self,
+        tokens_in: Vec<Address>,
+        tokens_out: Vec<Address>,
+        amount_in: Vec<U256>,
+        min_amount_out: Vec<U256>,
+    ) -> Result<RebalanceOutcome, Vec<u8>> {
+        self._only_keeper()?;
+        self._guard_drawdown()?;
+        self._check_cooldown()?;
+        let lengths = [tokens_in.len(), tokens_out.len(), amount_in.len(), min_amount_out.len()];
+        if lengths.iter().any(|length| *length != lengths[0]) {
+            return Err(VaultError::LengthMismatch(LengthMismatch { lengths: lengths.map(U256::from).to_vec() }).into());
+        }
+
+        let legs = tokens_in
+            .into_iter()
+            .zip(tokens_out)
+            .zip(amount_in.into_iter().zip(min_amount_out))
+            .map(|((token_in, token_out), (amount_in, min_amount_out))| Leg { token_in, token_out, amount_in, min_amount_out })
+            .collect();
+        let outcome = self._run_legs(legs)?;
+        if !outcome.1.is_zero() {
+            self._start_cooldown();
+        }
+        Ok(outcome)
+    }
+
+    /// Submits an order selling `amount_in` of `token_in` for `token_out`
+    /// in `slices` equal slices at least `interval` seconds apart. Returns
+    /// the order id (keeper or admin only).
+    pub fn submit_sliced_order(&mut self, token_in: Address, token_out: Address, amount_in: U256, slices: u16, interval: u64) -> Result<U256, Vec<u8>> {
+        self._only_keeper()?;
+        self._check_leg(&Leg { token_in, token_out, amount_in, min_amount_out: U256::ZERO })?;
+        if slices == 0 || amount_in < U256::from(slices) {
+            return Err(VaultError::InvalidSlicedOrder(InvalidSlicedOrder {}).into());
+        }
+
+        let order_id = U256::from(self.sliced_orders.len());
+        self.sliced_orders.grow().init(token_in, token_out, amount_in, slices, interval);
+        evm::log(SlicedOrderSubmitted { order_id, token_in, token_out, amount_in, slices, interval });
+        Ok(order_id)
+    }
+
+    /// Executes the next slice of a sliced order once it is due, bounded
+    /// by the oracle like any rebalance leg and spaced out from other
+    /// keeper trades by the minimum rebalance interval. Anyone may call it;
+    /// a failed swap reverts so the slice can be retried. Returns the
+    /// amount received.
+    pub fn execute_slice(&mut self, order_id: U256) -> Result<U256, Vec<u8>> {
+        self._guard_drawdown()?;
+        self._check_cooldown()?;
+        let order = self._sliced_order(order_id)?.state();
+        if order.is_closed() {
+            return Err(VaultError::OrderClosed(OrderClosed { order_id }).into());
+        }
+        let due_at = order.next_slice_at();
+        if block::timestamp() < due_at {
+            return Err(VaultError::SliceNotDue(SliceNotDue { order_id, due_at }).into());
+        }
+
+        let amount_in = order.next_slice_amount();
+        let leg = Leg { token_in: order.token_in, token_out: order.token_out, amount_in, min_amount_out: U256::ZERO };
+        let (results, succeeded) = self._run_legs(vec![leg])?;
+        if !succeeded.bit(0) {
+            return Err(VaultError::SliceFailed(SliceFailed { order_id }).into());
+        }
+        self._start_cooldown();
+
+        let amount_out = results[0].1;
+        if let Some(mut order) = self.sliced_orders.setter(order_id.to::<usize>()) {
+            order.record_slice(amount_out, block::timestamp());
+        }
+        evm::log(SliceExecuted { order_id, slice: order.slices_done + 1, amount_in, amount_out });
+        Ok(amount_out)
+    }
+
+    /// Stops a sliced order from executing further slices (keeper or admin only)
+    pub fn cancel_sliced_order(&mut self, order_id: U256) -> Result<(), VaultError> {
+        self._only_keeper()?;
+        self._sliced_order(order_id)?;
+        if let Some(mut order) = self.sliced_orders.setter(order_id.to::<usize>()) {
+            order.cancelled.set(true);
+        }
+        evm::log(SlicedOrderCancelled { order_id });
+        Ok(())
+    }
+
+    /// Progress of a sliced order
+    pub fn sliced_order(&self, order_id: U256) -> Result<SlicedOrderRow, VaultError> {
+        let order = self._sliced_order(order_id)?.state();
+        Ok((
+            order.token_in,
+            order.token_out,
+            order.amount_in,
+            order.amount_out,
+            order.slices,
+            order.slices_done,
+            order.interval,
+            order.last_executed,
+            order.cancelled,
+        ))
+    }
+
+    /// Registers a trigger selling `sell_bps` of the vault's balance of an
+    /// enabled `token` to USDC once its oracle price is at or below
+    /// `stop_price`, or at or above `take_price`; zero leaves a side out.
+    /// Returns the trigger id (keeper or admin only).
+    pub fn register_trigger(&mut self, token: Address, sell_bps: u16, stop_price: U256, take_price: U256) -> Result<U256, VaultError> {
+        self._only_keeper()?;
+        let trigger = TriggerState { token, sell_bps, stop_price, take_price, active: true };
+        let enabled = token != self.usdc_address.get() && self.basket.get(token).is_some_and(|config| config.enabled);
+        if !enabled || !trigger.is_valid() {
+            return Err(VaultError::InvalidTrigger(InvalidTrigger { token }));
+        }
+
+        let trigger_id = U256::from(self.triggers.len());
+        self.triggers.grow().init(trigger);
+        evm::log(TriggerRegistered { trigger_id, token, sell_bps, stop_price, take_price });
+        Ok(trigger_id)
+    }
+
+    /// Executes a trigger whose price condition holds, selling like a
+    /// rebalance leg, and pays the caller the bounty out of the proceeds.
+    /// Only a sale that brought in USDC pays, and the bounty counts against
+    /// the NAV drop and cash limits along with the sale. Triggers de-risk
+    /// into USDC, so they run even while the circuit breaker is tripped.
+    /// Returns the bounty paid.
+    pub fn execute_trigger(&mut self, trigger_id: U256) -> Result<U256, Vec<u8>> {
+        let trigger = self._trigger(trigger_id)?.state();
+        if !trigger.active {
+            return Err(VaultError::TriggerInactive(TriggerInactive { trigger_id }).into());
+        }
+        let price = self._quote(trigger.token)?.price;
+        if !trigger.fires_at(price) {
+            return Err(VaultError::TriggerNotMet(TriggerNotMet { trigger_id, price }).into());
+        }
+
+        // Fire once, before the swap calls out
+        if let Some(mut stored) = self.triggers.setter(trigger_id.to::<usize>()) {
+            stored.active.set(false);
+        }
+        let usdc_address = self.usdc_address.get();
+        let nav = self._total_assets()?;
+        let cash = self._balance_of(usdc_address)?;
+        let amount_in = trigger.sell_amount(self._balance_of(trigger.token)?);
+        let leg = Leg { token_in: trigger.token, token_out: usdc_address, amount_in, min_amount_out: U256::ZERO };
+        let (results, succeeded) = self._run_legs(vec![leg])?;
+        let amount_out = results[0].1;
+        if !succeeded.bit(0) || amount_out.is_zero() {
+            return Err(VaultError::TriggerFailed(TriggerFailed { trigger_id }).into());
+        }
+
+        let bounty = bounty(amount_out, self.trigger_bounty_bps.get().to::<u16>());
+        if !bounty.is_zero() {
+            self._transfer_usdc(msg::sender(), bounty)?;
+            self._check_payout(nav, cash)?;
+        }
+        evm::log(TriggerExecuted { trigger_id, executor: msg::sender(), price, amount_in, amount_out, bounty });
+        Ok(bounty)
+    }
+
+    /// Deactivates a trigger (keeper or admin only)
+    pub fn cancel_trigger(&mut self, trigger_id: U256) -> Result<(), VaultError> {
+        self._only_keeper()?;
+        self._trigger(trigger_id)?;
+        if let Some(mut trigger) = self.triggers.setter(trigger_id.to::<usize>()) {
+            trigger.active.set(false);
+        }
+        evm::log(TriggerCancelled { trigger_id });
+        Ok(())
+    }
+
+    /// Settings of a trigger and whether it may still fire
+    pub fn trigger(&self, trigger_id: U256) -> Result<TriggerRow, VaultError> {
+        let trigger = self._trigger(trigger_id)?.state();
+        Ok((trigger.token, trigger.sell_bps, trigger.stop_price, trigger.take_price, trigger.active))
+    }
+
+    /// Sets the share of a trigger's proceeds paid to whoever executes it,
+    /// at most 1% (admin only)
+    pub fn set_trigger_bounty(&mut self, bps: u16) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if bps > MAX_TRIGGER_BOUNTY_BPS {
+            return Err(VaultError::InvalidBounty(InvalidBounty { bps }));
+        }
+        self.trigger_bounty_bps.set(U16::from(bps));
+        Ok(())
+    }
+
+    /// Share of a trigger's proceeds paid to its executor, in bps
+    pub fn trigger_bounty(&self) -> u16 {
+        self.trigger_bounty_bps.get().to::<u16>()
+    }
+
+    /// Runs a rebalance order signed by the Token Metrics signer. Anyone may
+    /// relay the order; it must carry the vault's next nonce and be relayed
+    /// before its deadline.
+    pub fn rebalance_with_signature(
+        &mut self,
+        tokens_to_swap: Vec<Address>,
+        zero_to_one: Vec<bool>,
+        amount_in: Vec<U256>,
+        nonce: U256,
+        deadline: U256,
+        signature: Bytes,
+    ) -> Result<RebalanceOutcome, Vec<u8>> {
+        if U256::from(block::timestamp()) > deadline {
+            return Err(VaultError::OrderExpired(OrderExpired { deadline }).into());
+        }
+        if nonce != self.order_nonce.get() {
+            return Err(VaultError::InvalidNonce(InvalidNonce { nonce }).into());
+        }
+
+        let order = RebalanceOrder { tokens: tokens_to_swap, zeroToOne: zero_to_one, amountIn: amount_in, nonce, deadline };
+        let hash = signing_hash(&order, block::chainid(), contract::address());
+        if self._recover_signer(hash, &signature) != Some(self.metric_address.get()) {
+            return Err(VaultError::InvalidSignature(InvalidSignature {}).into());
+        }
+
+        // Spend the nonce before any swap calls out
+        self.order_nonce.set(nonce + U256::from(1));
+        evm::log(SignedRebalance { nonce, relayer: msg::sender() });
+        self._rebalance(order.tokens, order.zeroToOne, order.amountIn)
+    }
+
+    /// Commits to a rebalance without revealing it: `commitment` is the
+    /// keccak256 of the ABI-encoded `CommittedRebalance` (tokens,
+    /// directions, amounts and a secret salt) (keeper or admin only)
+    pub fn commit_rebalance(&mut self, commitment: FixedBytes<32>) -> Result<(), VaultError> {
+        self._only_keeper()?;
+        if self.commitments.get(commitment) != U64::ZERO {
+            return Err(VaultError::CommitmentExists(CommitmentExists { commitment }));
+        }
+        let block_number = block::number();
+        self.commitments.insert(commitment, U64::from(block_number));
+        evm::log(RebalanceCommitted { commitment, block_number });
+        Ok(())
+    }
+
+    /// Reveals and runs a committed rebalance once the reveal delay has
+    /// passed and before the commitment expires. Reverts unless the order
+    /// and salt hash to a live commitment (keeper or admin only).
+    pub fn reveal_rebalance(&mut self, tokens_to_swap: Vec<Address>, zero_to_one: Vec<bool>, amount_in: Vec<U256>, salt: FixedBytes<32>) -> Result<RebalanceOutcome, Vec<u8>> {
+        self._only_keeper()?;
+        let order = CommittedRebalance { tokens: tokens_to_swap, zeroToOne: zero_to_one, amountIn: amount_in, salt };
+        let commitment = commitment(&order);
+        let committed_at = self.commitments.get(commitment).to::<u64>();
+        if committed_at == 0 {
+            return Err(VaultError::CommitmentMismatch(CommitmentMismatch { commitment }).into());
+        }
+
+        let (delay, ttl) = self._reveal_window();
+        let (opens_at, expires_at) = reveal_window(committed_at, delay, ttl);
+        if block::number() < opens_at {
+            return Err(VaultError::RevealTooEarly(RevealTooEarly { opens_at }).into());
+        }
+        if block::number() > expires_at {
+            return Err(VaultError::CommitmentExpired(CommitmentExpired { expired_at: expires_at }).into());
+        }
+
+        // Spend the commitment before any swap calls out
+        self.commitments.delete(commitment);
+        evm::log(RebalanceRevealed { commitment });
+        self._rebalance(order.tokens, order.zeroToOne, order.amountIn)
+    }
+
+    /// Block a commitment was made in, zero if it is unknown or spent
+    pub fn commitment_block(&self, commitment: FixedBytes<32>) -> u64 {
+        self.commitments.get(commitment).to::<u64>()
+    }
+
+    /// Sets how many blocks a commitment must wait before it is revealed
+    /// and for how many blocks after that it may be (admin only)
+    pub fn set_reveal_window(&mut self, delay_blocks: u64, ttl_blocks: u64) -> Result<(), VaultError> {
+        self._only_admin()?;
+        if ttl_blocks == 0 {
+            return Err(VaultError::InvalidRevealWindow(InvalidRevealWindow {}));
+        }
+        self.reveal_delay_blocks.set(U64::from(delay_blocks));
+        self.reveal_ttl_blocks.set(U64::from(ttl_blocks));
+        Ok(())
+    }
+
+    /// Reveal delay and time to live of commitments, in blocks
+    pub fn reveal_window(&self) -> (u64, u64) {
+        self._reveal_window()
+    }
+
+    /// Nonce the next signed rebalance order must carry
+    pub fn order_nonce(&self) -> U256 {
+        self.order_nonce.get()
     }
 }
 
 // internal functions   
 impl Vault {
 
+    /// Swaps each of `tokens_to_swap` to USDC or USDC to it, as directed
+    pub fn _rebalance(&mut self, tokens_to_swap: Vec<Address>, zero_to_one: Vec<bool>, amount_in: Vec<U256>) -> Result<RebalanceOutcome, Vec<u8>> {
+        self._guard_drawdown()?;
+        self._check_cooldown()?;
+        let usdc_address = self.usdc_address.get();
+        let lengths = [tokens_to_swap.len(), zero_to_one.len(), amount_in.len()];
+        if lengths.iter().any(|length| *length != lengths[0]) {
+            return Err(VaultError::LengthMismatch(LengthMismatch { lengths: lengths.map(U256::from).to_vec() }).into());
+        }
+
+        let legs = tokens_to_swap
+            .into_iter()
+            .zip(zero_to_one)
+            .zip(amount_in)
+            .map(|((token, is_zero_to_one), amount_in)| {
+                let (token_in, token_out) = if is_zero_to_one { (token, usdc_address) } else { (usdc_address, token) };
+                Leg { token_in, token_out, amount_in, min_amount_out: U256::ZERO }
+            })
+            .collect();
+        let outcome = self._run_legs(legs)?;
+        if !outcome.1.is_zero() {
+            self._start_cooldown();
+        }
+        Ok(outcome)
+    }
+
+    /// Runs the legs of a rebalance. The whole batch is checked before any
+    /// leg runs; each leg's balance is checked as it runs, so earlier legs
+    /// may fund later ones. Only a rebalance that traded is held to the risk
+    /// limits and recorded in the epoch.
+    pub fn _run_legs(&mut self, legs: Vec<Leg>) -> Result<RebalanceOutcome, Vec<u8>> {
+        let gas_start = evm::gas_left();
+        let usdc_address = self.usdc_address.get();
+        if legs.is_empty() {
+            return Err(VaultError::NoLegs(NoLegs {}).into());
+        }
+        if legs.len() > MAX_LEGS {
+            return Err(VaultError::TooManyLegs(TooManyLegs { legs: U256::from(legs.len()) }).into());
+        }
+        for (index, leg) in legs.iter().enumerate() {
+            self._check_leg(leg)?;
+            if leg.amount_in.is_zero() {
+                return Err(VaultError::ZeroAmount(ZeroAmount { leg: U256::from(index) }).into());
+            }
+        }
+
+        let nav = self._total_assets()?;
+        let cash = self._balance_of(usdc_address)?;
+        let mut traded = U256::ZERO;
+        let mut bought = Vec::new();
+
+        let mut results = Vec::with_capacity(legs.len());
+        let mut succeeded = U256::ZERO;
+        for (index, leg) in legs.into_iter().enumerate() {
+            let Leg { token_in, token_out, amount_in, min_amount_out } = leg;
+            let balance = self._balance_of(token_in)?;
+            if balance < amount_in {
+                return Err(VaultError::InsufficientTokenBalance(InsufficientTokenBalance { token: token_in, balance, amount: amount_in }).into());
+            }
+
+            // Swap no worse than the oracle or the caller allow
+            let (oracle_minimum, sqrt_price_limit_x96) = self._swap_bounds(token_in, token_out, amount_in)?;
+            match self._swap_pair(token_in, token_out, amount_in, min_amount_out.max(oracle_minimum), sqrt_price_limit_x96) {
+                Ok(amount_out) => {
+                    evm::log(Rebalance { token_in, token_out, amount_in, amount_out });
+                    results.push((amount_in, amount_out));
+                    succeeded = mark_succeeded(succeeded, index);
+                    traded += expected_out(amount_in, self._quote(token_in)?, Quote::USDC);
+                    if token_out != usdc_address {
+                        bought.push(token_out);
+                    }
+                }
+                Err(data) => {
+                    self._rebalance_swap_failed(index, token_in, token_out, amount_in, data)?;
+                    results.push((amount_in, U256::ZERO));
+                }
+            }
+        }
+        if !succeeded.is_zero() {
+            self._check_risk(nav, cash, &bought, traded, gas_start)?;
+        }
+        Ok((results, succeeded))
+    }
+
+    /// Reverts if the rebalance that started at `nav_before` with
+    /// `cash_before` USDC, bought `bought` and traded `traded` USDC of value
+    /// breached a risk limit; otherwise records it in the current epoch,
+    /// opening a new one from `nav_before` if it is over
+    pub fn _check_risk(&mut self, nav_before: U256, cash_before: U256, bought: &[Address], traded: U256, gas_start: u64) -> Result<(), VaultError> {
+        self._open_epoch(nav_before);
+        let limits = self._risk_limits();
+        let nav = self._total_assets()?;
+        let cash = self._balance_of(self.usdc_address.get())?;
+        let epoch = self.epochs.getter(self.epoch_id.get()).state();
+
+        check_nav_drop(&limits, nav_before, nav).map_err(|breach| Self::_breach(Address::ZERO, breach))?;
+        check_cash(&limits, cash_before, cash, nav).map_err(|breach| Self::_breach(Address::ZERO, breach))?;
+        check_turnover(&limits, epoch.turnover + traded, epoch.start_nav).map_err(|breach| Self::_breach(Address::ZERO, breach))?;
+        for token in bought {
+            let max_weight_bps = self.basket.get(*token).map_or(BPS_DENOMINATOR, |config| config.max_weight_bps);
+            let value = self._holdings(&[*token])?[0].value;
+            check_weight(value, nav, max_weight_bps).map_err(|breach| Self::_breach(*token, breach))?;
+        }
+
+        let gas_used = gas_start.saturating_sub(evm::gas_left());
+        self.epochs.setter(self.epoch_id.get()).record(nav, traded, gas_used);
+        Ok(())
+    }
+
+    /// Reverts if what a rebalance that started at `nav_before` with
+    /// `cash_before` USDC traded and paid out together broke the NAV drop
+    /// or cash limits
+    pub fn _check_payout(&self, nav_before: U256, cash_before: U256) -> Result<(), VaultError> {
+        let limits = self._risk_limits();
+        let nav = self._total_assets()?;
+        let cash = self._balance_of(self.usdc_address.get())?;
+        check_nav_drop(&limits, nav_before, nav).map_err(|breach| Self::_breach(Address::ZERO, breach))?;
+        check_cash(&limits, cash_before, cash, nav).map_err(|breach| Self::_breach(Address::ZERO, breach))?;
+        Ok(())
+    }
+
+    /// Starts a new epoch from `nav` if the current one is over
+    pub fn _open_epoch(&mut self, nav: U256) {
+        let epoch_id = self.epoch_id.get();
+        let started_at = self.epochs.getter(epoch_id).started_at.get().to::<u64>();
+        if !epoch_over(started_at, self._epoch_length(), block::timestamp()) {
+            return;
+        }
+        let epoch_id = epoch_id + U256::from(1);
+        self.epoch_id.set(epoch_id);
+        self.epochs.setter(epoch_id).open(block::timestamp(), nav);
+        evm::log(EpochStarted { epoch_id, start_nav: nav });
+    }
+
+    /// Reveal delay and time to live of commitments, in blocks
+    pub fn _reveal_window(&self) -> (u64, u64) {
+        match self.reveal_ttl_blocks.get().to::<u64>() {
+            0 => (DEFAULT_REVEAL_DELAY, DEFAULT_REVEAL_TTL),
+            ttl => (self.reveal_delay_blocks.get().to::<u64>(), ttl),
+        }
+    }
+
+    /// Length of an epoch in seconds
+    pub fn _epoch_length(&self) -> u64 {
+        match self.epoch_length.get().to::<u64>() {
+            0 => DEFAULT_EPOCH_LENGTH,
+            seconds => seconds,
+        }
+    }
+
+    /// Reverts if the last rebalance that traded ran less than the minimum
+    /// interval ago; only checks, `_start_cooldown` records a rebalance
+    pub fn _check_cooldown(&self) -> Result<(), VaultError> {
+        let next_at = next_rebalance_at(self.last_rebalance_at.get().to::<u64>(), self.min_rebalance_interval.get().to::<u64>());
+        if block::timestamp() < next_at {
+            return Err(VaultError::RebalanceCooldown(RebalanceCooldown { next_at }));
+        }
+        Ok(())
+    }
+
+    /// Starts the cooldown after a keeper rebalance that traded
+    pub fn _start_cooldown(&mut self) {
+        self.last_rebalance_at.set(U64::from(block::timestamp()));
+    }
+
+    /// Error naming the limit `breach` broke, `token` being the one bought
+    /// over its weight cap
+    pub fn _breach(token: Address, breach: Breach) -> VaultError {
+        match breach {
+            Breach::MaxWeight { weight_bps, max_weight_bps } => VaultError::MaxWeightExceeded(MaxWeightExceeded { token, weight_bps, max_weight_bps }),
+            Breach::CashBuffer { cash_bps, min_cash_bps } => VaultError::CashBufferBreached(CashBufferBreached { cash_bps, min_cash_bps }),
+            Breach::Turnover { turnover, max_turnover } => VaultError::TurnoverExceeded(TurnoverExceeded { turnover, max_turnover }),
+            Breach::NavDrop { drop_bps, max_nav_drop_bps } => VaultError::NavDropExceeded(NavDropExceeded { drop_bps, max_nav_drop_bps }),
+        }
+    }
+
+    /// Risk limits every rebalance is checked against
+    pub fn _risk_limits(&self) -> RiskLimits {
+        RiskLimits {
+            min_cash_bps: self.min_cash_bps.get().to::<u16>(),
+            max_turnover_bps: self.max_turnover_bps.get().to::<u16>(),
+            max_nav_drop_bps: self.max_nav_drop_bps.get().to::<u16>(),
+        }
+    }
+
+    /// Reverts unless `leg` sells USDC or a basket token for USDC or an
+    /// enabled basket token; disabled tokens may still be sold off
+    pub fn _check_leg(&self, leg: &Leg) -> Result<(), VaultError> {
+        let usdc_address = self.usdc_address.get();
+        if leg.token_in == leg.token_out {
+            return Err(VaultError::TokenNotEnabled(TokenNotEnabled { token: leg.token_out }));
+        }
+        if leg.token_in != usdc_address && self.basket.get(leg.token_in).is_none() {
+            return Err(VaultError::TokenNotEnabled(TokenNotEnabled { token: leg.token_in }));
+        }
+        if leg.token_out != usdc_address && !self.basket.get(leg.token_out).is_some_and(|config| config.enabled) {
+            return Err(VaultError::TokenNotEnabled(TokenNotEnabled { token: leg.token_out }));
+        }
+        // Token-to-token legs need a route a single venue can take
+        if leg.token_in != usdc_address && leg.token_out != usdc_address {
+            self._pair_route(leg.token_in, leg.token_out)?;
+        }
+        Ok(())
+    }
+
+    /// Reverts while the circuit breaker is tripped or NAV per share is
+    /// past the drawdown threshold, otherwise records a new peak. The
+    /// revert undoes any trip, so anyone may call `check_drawdown` to trip
+    /// the breaker for good.
+    pub fn _guard_drawdown(&mut self) -> Result<(), VaultError> {
+        if self.breaker_tripped.get() {
+            return Err(VaultError::RebalancingPaused(RebalancingPaused {}));
+        }
+        let Some(price) = self._share_price()? else {
+            return Ok(());
+        };
+        let peak = self.peak_share_price.get();
+        if breached(price, peak, self.max_drawdown_bps.get().to::<u16>()) {
+            return Err(VaultError::DrawdownExceeded(DrawdownExceeded { nav: price, peak }));
+        }
+        if price > peak {
+            self.peak_share_price.set(price);
+        }
+        Ok(())
+    }
+
+    /// Sliced order `order_id`
+    pub fn _sliced_order(&self, order_id: U256) -> Result<StorageGuard<'_, SlicedOrder>, VaultError> {
+        let index = usize::try_from(order_id).ok();
+        index
+            .and_then(|index| self.sliced_orders.get(index))
+            .ok_or(VaultError::UnknownOrder(UnknownOrder { order_id }))
+    }
+
+    /// Price trigger `trigger_id`
+    pub fn _trigger(&self, trigger_id: U256) -> Result<StorageGuard<'_, PriceTrigger>, VaultError> {
+        let index = usize::try_from(trigger_id).ok();
+        index
+            .and_then(|index| self.triggers.get(index))
+            .ok_or(VaultError::UnknownTrigger(UnknownTrigger { trigger_id }))
+    }
+
+    /// NAV per share, scaled by 1e18; `None` while the vault has no shares
+    pub fn _share_price(&self) -> Result<Option<U256>, VaultError> {
+        Ok(share_price(self._total_assets()?, self.erc20.total_supply()))
+    }
+
+    /// NAV per share to carry the drawdown peak across a deposit with;
+    /// `None` while the breaker is off or the basket cannot be valued, so
+    /// that deposits never depend on the oracle
+    pub fn _tracked_share_price(&self) -> Option<Option<U256>> {
+        if self.max_drawdown_bps.get().is_zero() {
+            return None;
+        }
+        self._share_price().ok()
+    }
+
+    /// Reverts unless the caller is the vault admin
+    pub fn _only_admin(&self) -> Result<(), VaultError> {
+        if msg::sender() != self.admin.get() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
+        }
+        Ok(())
+    }
+
+    /// Reverts unless the caller is the Token Metrics keeper or the admin
+    pub fn _only_keeper(&self) -> Result<(), VaultError> {
+        if msg::sender() != self.metric_address.get() && msg::sender() != self.admin.get() {
+            return Err(VaultError::NotAuthorized(NotAuthorized {}));
+        }
+        Ok(())
+    }
+
+    /// Signer of `hash`, recovered through the `ecrecover` precompile
+    pub fn _recover_signer(&self, hash: FixedBytes<32>, signature: &[u8]) -> Option<Address> {
+        let input = ecrecover_input(hash, signature)?;
+        static_call(self, ECRECOVER, &input).ok().and_then(|output| decode_ecrecover(&output))
+    }
+
+    /// Balance of `token` held by the vault
+    pub fn _balance_of(&self, token: Address) -> Result<U256, VaultError> {
+        static_call_contract(self, token, &IERC20::balanceOfCall { account: contract::address() })
+            .map(|ret| ret._0)
+            .map_err(|_| VaultError::BalanceQueryFailed(BalanceQueryFailed { token }))
+    }
+
+    /// Balances of `tokens` held by the vault and their USDC value
+    pub fn _holdings(&self, tokens: &[Address]) -> Result<Vec<Holding>, VaultError> {
+        let mut holdings = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let balance = self._balance_of(*token)?;
+            let quote = self._quote(*token)?;
+            holdings.push(Holding { balance, value: token_value(balance, quote.price, quote.decimals) });
+        }
+        Ok(holdings)
+    }
+
+    /// Tolerance band of `rebalance_to_weights`, in bps of NAV
+    pub fn _weight_tolerance_bps(&self) -> u16 {
+        match self.weight_tolerance_bps.get().to::<u16>() {
+            0 => DEFAULT_WEIGHT_TOLERANCE_BPS,
+            bps => bps,
+        }
+    }
+
+    /// Values the vault's balances of every basket token in USDC, with the
+    /// token's own oracle if it has one
+    pub fn _total_assets(&self) -> Result<U256, VaultError> {
+        let usdc_address = self.usdc_address.get();
+        let mut total = U256::ZERO;
+
+        for config in self.basket.configs() {
+            let token = config.token;
+            let balance = self._balance_of(token)?;
+            if balance.is_zero() {
+                continue;
+            }
+
+            if token == usdc_address {
+                total += balance;
+                continue;
+            }
+
+            let quote = self._quote(token)?;
+            total += token_value(balance, quote.price, quote.decimals);
+        }
+
+        Ok(total)
+    }
+
+    /// Redeems `amount_out` shares of `owner`: sells their share of every
+    /// basket token to USDC, burns the shares and returns the USDC owed
+    pub fn _redeem(&mut self, owner: Address, amount_out: U256) -> Result<U256, VaultError> {
+        // Calculate the % of the supply that the user has with scaling to maintain precision
+        let supply = self.erc20.total_supply();
+        // Use a scaling factor of 10^18 to handle decimal percentages
+        let scaling_factor = U256::from(10).pow(U256::from(18));
+        let percentage = (amount_out * scaling_factor) / supply;
+        
+        let usdc_address = self.usdc_address.get();
+
+        // USDC owed to the user, including the output of every swap
+        let mut proceeds = U256::ZERO;
+        
+        // Iterate the basket tokens manually, disabled ones included
+        let mut i = 0;
+        loop {
+            // Try to get the token at the current index
+            let token_opt = self.basket.at(i).map(|config| config.token);
+            
+            // If we get None, we've reached the end of the array
+            if token_opt.is_none() {
+                break;
+            }
+            
+            let token = token_opt.unwrap();
+            
+            // Get the balance of this token that the contract owns
+            let balance_of = IERC20::balanceOfCall { account: contract::address() };
+            let config = Call::new_in(self).gas(evm::gas_left() / 2);
+            
+            // Try to get the token balance, handle errors properly
+            let token_balance = match static_call_contract(config, token, &balance_of) {
+                Ok(ret) => ret._0,
+                Err(_) => {
+                    // Skip this token if balance check fails, continue to next token
+                    i += 1;
+                    continue;
+                }
+            };
+            
+            // Calculate the amount to transfer based on user's percentage
+            // Divide by scaling factor to get actual amount
+            let share_total = (token_balance * percentage) / scaling_factor;
+            
+            if share_total > U256::ZERO {
+                if token != usdc_address {
+                    // For non-USDC tokens, swap to USDC - skip the token if it fails
+                    match self._swap_to_usdc(token, share_total, U256::ZERO, U256::ZERO) {
+                        Ok(amount_out) => proceeds += amount_out,
+                        Err(data) => Self::_log_swap_failure(token, usdc_address, share_total, &data),
+                    }
+                } else {
+                    // USDC is paid out as is
+                    proceeds += share_total;
+                }
+            }
+            
+            i += 1;
+        }
+        
+        // Burn the user's tokens after all swaps are done
+        self.erc20.burn(owner, amount_out)?;
+
+        Ok(proceeds)
+    }
+
+    /// Withholds tax on the gain over `basis` and pays out the rest of the
+    /// `proceeds` of `shares` redeemed by `owner`
+    pub fn _settle_withdrawal(&mut self, owner: Address, shares: U256, proceeds: U256, basis: U256) -> Result<(), VaultError> {
+        let withheld = self._withhold_tax(owner, proceeds, basis)?;
+        evm::log(Withdraw { owner, shares, assets: proceeds });
+
+        // Pay the user what is left in USDC
+        self._transfer_usdc(owner, proceeds - withheld)
+    }
+
+    /// Router flavour behind `router_address`
+    pub fn _router_kind(&self) -> RouterKind {
+        RouterKind::from_u8(self.router_kind.get().to::<u8>()).unwrap_or(RouterKind::SwapRouter02)
+    }
+
+    /// Venue swaps between `token_a` and `token_b` go through, Uniswap V3
+    /// unless the pair was given one
+    pub fn _pair_venue(&self, token_a: Address, token_b: Address) -> Venue {
+        Venue::from_u8(self.pair_venues.getter(token_a).get(token_b).to::<u8>()).unwrap_or(Venue::UniswapV3)
+    }
+
+    /// Router of `venue`
+    pub fn _venue_router(&self, venue: Venue) -> Address {
+        match venue {
+            Venue::UniswapV3 => self.router_address.get(),
+            _ => self.venue_routers.get(U8::from(venue as u8)),
+        }
+    }
+
+    /// Accounting mode the vault runs in
+    pub fn _accounting_mode(&self) -> AccountingMode {
+        AccountingMode::from_u8(self.accounting_mode.get().to::<u8>()).unwrap_or(AccountingMode::Lots)
+    }
+
+    /// Reverts unless the vault runs in `mode`. In receipt mode shares only
+    /// move together with their receipt.
+    pub fn _require_mode(&self, mode: AccountingMode) -> Result<(), VaultError> {
+        if self._accounting_mode() != mode {
+            return Err(VaultError::WrongAccountingMode(WrongAccountingMode { mode: self._accounting_mode() as u8 }));
+        }
+        Ok(())
+    }
+
+    /// Mints the next deposit receipt to `to` and returns its id
+    pub fn _mint_receipt(&mut self, to: Address) -> Result<U256, VaultError> {
+        let receipts = self.receipts_address.get();
+        let config = Call::new_in(self).gas(evm::gas_left() / 2);
+        call_contract(config, receipts, &IReceipts::mintCall { to })
+            .map(|ret| ret._0)
+            .map_err(|_| VaultError::ReceiptsFailed(ReceiptsFailed { receipts }))
+    }
+
+    /// Burns deposit receipt `receipt_id`
+    pub fn _burn_receipt(&mut self, receipt_id: U256) -> Result<(), VaultError> {
+        let receipts = self.receipts_address.get();
+        let config = Call::new_in(self).gas(evm::gas_left() / 2);
+        call_contract(config, receipts, &IReceipts::burnCall { tokenId: receipt_id })
+            .map(|_| ())
+            .map_err(|_| VaultError::ReceiptsFailed(ReceiptsFailed { receipts }))
+    }
+
+    /// Owner of deposit receipt `receipt_id`
+    pub fn _receipt_owner(&self, receipt_id: U256) -> Result<Address, VaultError> {
+        let receipts = self.receipts_address.get();
+        static_call_contract(self, receipts, &IReceipts::ownerOfCall { tokenId: receipt_id })
+            .map(|ret| ret._0)
+            .map_err(|_| VaultError::ReceiptsFailed(ReceiptsFailed { receipts }))
+    }
+
+    /// Sends `amount` USDC held by the vault to `to`
+    pub fn _transfer_usdc(&mut self, to: Address, amount: U256) -> Result<(), VaultError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        let usdc_address = self.usdc_address.get();
+        let config = Call::new_in(self).gas(evm::gas_left() / 2);
+        match call_contract(config, usdc_address, &IERC20::transferCall { recipient: to, amount }) {
+            Ok(ret) if ret._0 => Ok(()),
+            _ => Err(VaultError::TransferFailed(TransferFailed { token: usdc_address })),
+        }
+    }
+
+    /// Diverts the opted-in share of the gain realized by `owner` to the
+    /// withholding treasury and returns the amount withheld
+    pub fn _withhold_tax(&mut self, owner: Address, proceeds: U256, basis: U256) -> Result<U256, VaultError> {
+        let rate = self.withholding_rates.get(owner).to::<u16>();
+        let treasury = self.withholding_treasury.get();
+        if rate == 0 || treasury == Address::ZERO {
+            return Ok(U256::ZERO);
+        }
+
+        let gain = realized_gain(proceeds, basis);
+        let withheld = withholding_amount(gain, rate);
+        if withheld.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        self._transfer_usdc(treasury, withheld)?;
+        evm::log(TaxWithheld { owner, treasury, proceeds, gain, withheld });
+        Ok(withheld)
+    }
+
    
     /// Mints tokens to another address
     pub fn _mint_to(&mut self, to: Address, value: U256) -> Result<(), Erc20Error> {
@@ -296,57 +1897,6 @@ impl Vault {
         Ok(())
     }
 
-    /// Constructs calldata for the exactInputSingle function
-    pub fn _create_swap_calldata(
-        &self, 
-        token_in: Address,
-        token_out: Address,
-        fee: u32,
-        recipient: Address, 
-        amount_in: U256, 
-        amount_out_minimum: U256,
-        sqrt_price_limit_x96: U256
-    ) -> Vec<u8> {
-        // Function signature for exactInputSingle (0x04e45aaf)
-        let mut calldata = vec![0x04, 0xe4, 0x5a, 0xaf];
-        
-        // For a tuple parameter, we only need the function selector followed by the tuple values
-        // No need for an offset since it's a direct tuple, not a dynamic type
-        
-        // First member: tokenIn (address)
-        let mut token_in_bytes = [0u8; 32];
-        token_in_bytes[12..].copy_from_slice(token_in.as_slice());
-        calldata.extend_from_slice(&token_in_bytes);
-        
-        // Second member: tokenOut (address)
-        let mut token_out_bytes = [0u8; 32];
-        token_out_bytes[12..].copy_from_slice(token_out.as_slice());
-        calldata.extend_from_slice(&token_out_bytes);
-        
-        // Third member: fee (uint24) - needs to be padded to 32 bytes
-        let mut fee_bytes = [0u8; 32];
-        fee_bytes[29] = ((fee >> 16) & 0xFF) as u8;
-        fee_bytes[30] = ((fee >> 8) & 0xFF) as u8;
-        fee_bytes[31] = (fee & 0xFF) as u8;
-        calldata.extend_from_slice(&fee_bytes);
-        
-        // Fourth member: recipient (address)
-        let mut recipient_bytes = [0u8; 32];
-        recipient_bytes[12..].copy_from_slice(recipient.as_slice());
-        calldata.extend_from_slice(&recipient_bytes);
-        
-        // Fifth member: amountIn (uint256)
-        calldata.extend_from_slice(&amount_in.to_be_bytes::<32>());
-        
-        // Sixth member: amountOutMinimum (uint256)
-        calldata.extend_from_slice(&amount_out_minimum.to_be_bytes::<32>());
-        
-        // Seventh member: sqrtPriceLimitX96 (uint160) - padded to 32 bytes
-        calldata.extend_from_slice(&sqrt_price_limit_x96.to_be_bytes::<32>());
-        
-        calldata
-    }
-
     pub fn _execute_swap(
         &mut self,
         contract: Address,
@@ -365,69 +1915,533 @@ impl Vault {
         Ok(return_data)
     }
 
-    /// Helper function to perform an exact input swap
-    pub fn _swap_exact_input(
-        &mut self,
-        token_in: Address,
-        token_out: Address,
-        fee: u32,
-        recipient: Address, 
-        amount_in: U256, 
-        amount_out_minimum: U256,
-        sqrt_price_limit_x96: U256
-    ) -> Result<U256, Vec<u8>> {
-        // Get the router address
-        let router = self.router_address.get();
-        
-        // Create the calldata for the swap
-        let calldata = self._create_swap_calldata(
-            token_in,
-            token_out,
-            fee,
-            recipient,
-            amount_in,
-            amount_out_minimum,
-            sqrt_price_limit_x96
-        );
-        
-        // Execute the swap
-        let return_data = self._execute_swap(router, calldata)?;
+    /// Helper function to perform an exact input swap on the venue of the pair
+    pub fn _swap_exact_input(&mut self, swap: ExactInputSingle) -> Result<U256, Vec<u8>> {
+        // Get the venue of the pair and its router
+        let venue = self._pair_venue(swap.token_in, swap.token_out);
+        let router = self._venue_router(venue);
+        let adapter = venue.adapter(self._router_kind());
+
+        // Encode for the venue, deadline-taking routers get the current block
+        let calldata = adapter.encode_exact_input_single(&swap, U256::from(block::timestamp()));
+
+        // Let the router pull the input and execute the swap
+        let return_data = self._swap_with_approval(swap.token_in, router, swap.amount_in, calldata)?;
         
-        // Parse the return data (uint256 amountOut)
-        if return_data.len() >= 32 {
-            let mut amount_out_bytes = [0u8; 32];
-            amount_out_bytes.copy_from_slice(&return_data[0..32]);
-            let amount_out = U256::from_be_bytes(amount_out_bytes);
-            Ok(amount_out)
-        } else {
-            Err(return_data)
+        // Parse the return data for the amount received
+        adapter.decode_amount_out(&return_data).ok_or(return_data)
+    }
+
+    /// Helper function to perform an exact input swap along a packed path
+    /// on `venue`
+    pub fn _swap_path(&mut self, venue: Venue, path: Vec<u8>, amount_in: U256, amount_out_minimum: U256) -> Result<U256, Vec<u8>> {
+        let router = self._venue_router(venue);
+        let adapter = venue.adapter(self._router_kind());
+        let (tokens, _) = decode_path(&path).ok_or_else(Vec::new)?;
+
+        let swap = ExactInput { path, recipient: contract::address(), amount_in, amount_out_minimum };
+        let calldata = adapter.encode_exact_input(&swap, U256::from(block::timestamp()));
+        let return_data = self._swap_with_approval(tokens[0], router, amount_in, calldata)?;
+        adapter.decode_amount_out(&return_data).ok_or(return_data)
+    }
+
+    /// Helper function to perform an exact output swap on the venue of the
+    /// pair. Approves at most `amount_in_maximum`, resets whatever the
+    /// router did not pull and returns the amount of `token_in` spent.
+    pub fn _swap_exact_output_single(&mut self, swap: ExactOutputSingle) -> Result<U256, Vec<u8>> {
+        let venue = self._pair_venue(swap.token_in, swap.token_out);
+        let router = self._venue_router(venue);
+        let adapter = venue.adapter(self._router_kind());
+
+        let calldata = adapter.encode_exact_output_single(&swap, U256::from(block::timestamp()));
+        let return_data = self._swap_with_approval(swap.token_in, router, swap.amount_in_maximum, calldata)?;
+        adapter.decode_amount_in(&return_data).ok_or(return_data)
+    }
+
+    /// Helper function to perform an exact output swap on `venue` along a
+    /// packed path, which runs from the output token back to the input
+    /// token. Returns the amount of the input token spent.
+    pub fn _swap_exact_output_path(&mut self, venue: Venue, path: Vec<u8>, amount_out: U256, amount_in_maximum: U256) -> Result<U256, Vec<u8>> {
+        let router = self._venue_router(venue);
+        let adapter = venue.adapter(self._router_kind());
+        let (tokens, _) = decode_path(&path).ok_or_else(Vec::new)?;
+        let token_in = tokens[tokens.len() - 1];
+
+        let swap = ExactOutput { path, recipient: contract::address(), amount_out, amount_in_maximum };
+        let calldata = adapter.encode_exact_output(&swap, U256::from(block::timestamp()));
+        let return_data = self._swap_with_approval(token_in, router, amount_in_maximum, calldata)?;
+        adapter.decode_amount_in(&return_data).ok_or(return_data)
+    }
+
+    /// Buys exactly `amount_out` USDC with at most `amount_in_maximum` of
+    /// `token`, along its configured path if any, on the venue of the
+    /// token's USDC pair. Returns the amount spent.
+    pub fn _swap_to_exact_usdc(&mut self, token: Address, amount_out: U256, amount_in_maximum: U256) -> Result<U256, Vec<u8>> {
+        let usdc_address = self.usdc_address.get();
+        let path = self.paths_to_usdc.getter(token).get_bytes();
+        if path.is_empty() {
+            let swap = ExactOutputSingle {
+                token_in: token,
+                token_out: usdc_address,
+                fee: self._token_fee(token),
+                recipient: contract::address(),
+                amount_out,
+                amount_in_maximum,
+                sqrt_price_limit_x96: U256::ZERO,
+            };
+            return self._swap_exact_output_single(swap);
         }
+        let venue = self._pair_venue(token, usdc_address);
+        let path = reverse_path(&path).ok_or_else(Vec::new)?;
+        self._swap_exact_output_path(venue, path, amount_out, amount_in_maximum)
+    }
+
+    /// Approves `router` for `amount` of `token_in`, sends it the swap in
+    /// `calldata` and clears whatever allowance the router left
+    pub fn _swap_with_approval(&mut self, token_in: Address, router: Address, amount: U256, calldata: Vec<u8>) -> Result<Vec<u8>, Vec<u8>> {
+        self._approve(token_in, router, amount)?;
+        let result = self._execute_swap(router, calldata);
+
+        // The router may pull less than approved, never leave the rest approved
+        self._approve(token_in, router, U256::ZERO)?;
+        result
     }
 
-    /// Helper function to perform an exact input swap with some default values
+    /// Sets the vault's allowance of `spender` on `token` to `amount`
+    pub fn _approve(&mut self, token: Address, spender: Address, amount: U256) -> Result<(), VaultError> {
+        let config = Call::new_in(self).gas(evm::gas_left() / 2);
+        match call_contract(config, token, &IERC20::approveCall { spender, amount }) {
+            Ok(ret) if ret._0 => Ok(()),
+            _ => Err(VaultError::ApproveFailed(ApproveFailed { token })),
+        }
+    }
+
+    /// Oracle quote of `token`, from the token's own oracle if it has one.
+    /// USDC is worth its face value.
+    pub fn _quote(&self, token: Address) -> Result<Quote, VaultError> {
+        if token == self.usdc_address.get() {
+            return Ok(Quote::USDC);
+        }
+
+        let failed = || VaultError::OracleFailed(OracleFailed { token });
+        let (oracle, decimals) = match self.basket.get(token) {
+            Some(config) if config.oracle != Address::ZERO => (config.oracle, config.decimals),
+            Some(config) => (self.oracle_address.get(), config.decimals),
+            None => {
+                let decimals = static_call_contract(self, token, &IERC20::decimalsCall {}).map_err(|_| failed())?._0;
+                (self.oracle_address.get(), decimals)
+            }
+        };
+        let price = static_call_contract(self, oracle, &IPriceOracle::getPriceCall { token }).map_err(|_| failed())?._0;
+        Ok(Quote { price, decimals })
+    }
+
+    /// Slippage allowed on rebalance legs, in bps
+    pub fn _max_slippage_bps(&self) -> u16 {
+        match self.max_slippage_bps.get().to::<u16>() {
+            0 => DEFAULT_MAX_SLIPPAGE_BPS,
+            bps => bps,
+        }
+    }
+
+    /// Minimum output and `sqrtPriceLimitX96` of swapping `amount_in` of
+    /// `token_in` for `token_out`, both `max_slippage_bps` off the oracle
+    pub fn _swap_bounds(&self, token_in: Address, token_out: Address, amount_in: U256) -> Result<(U256, U256), VaultError> {
+        let quote_in = self._quote(token_in)?;
+        let quote_out = self._quote(token_out)?;
+
+        // Without a price the leg would go unprotected
+        for (token, quote) in [(token_in, quote_in), (token_out, quote_out)] {
+            if quote.price.is_zero() {
+                return Err(VaultError::OracleFailed(OracleFailed { token }));
+            }
+        }
+
+        let slippage = self._max_slippage_bps();
+        let expected = expected_out(amount_in, quote_in, quote_out);
+        Ok((
+            min_amount_out(expected, slippage),
+            sqrt_price_limit_x96(token_in, quote_in, token_out, quote_out, slippage),
+        ))
+    }
+
+    /// Logs why a swap of `amount_in` from `token_in` to `token_out` failed
+    pub fn _log_swap_failure(token_in: Address, token_out: Address, amount_in: U256, data: &[u8]) {
+        let reason = decode_revert(data).to_string();
+        evm::log(SwapFailed { token_in, token_out, amount_in, reason });
+    }
+
+    /// Handles a failed rebalance leg: reverts with `LegReverted` in atomic
+    /// mode, otherwise logs it so the next legs still run
+    pub fn _rebalance_swap_failed(&self, leg: usize, token_in: Address, token_out: Address, amount_in: U256, data: Vec<u8>) -> Result<(), Vec<u8>> {
+        let leg = U256::from(leg);
+        let reason = decode_revert(&data).to_string();
+        if self._rebalance_mode() == RebalanceMode::Atomic {
+            return Err(VaultError::LegReverted(LegReverted { leg, token_in, token_out, reason }).into());
+        }
+        evm::log(LegFailed { leg, token_in, token_out, amount_in, reason });
+        Ok(())
+    }
+
+    /// What rebalances do when a leg fails
+    pub fn _rebalance_mode(&self) -> RebalanceMode {
+        RebalanceMode::from_u8(self.rebalance_mode.get().to::<u8>()).unwrap_or(RebalanceMode::BestEffort)
+    }
+
+    /// Fee tier of the USDC pool of `token`
+    pub fn _token_fee(&self, token: Address) -> u32 {
+        self.basket.get(token).map_or(DEFAULT_FEE, |config| config.fee)
+    }
+
+    /// Swaps `amount_in` of `token` to USDC, along its configured path if
+    /// any. The price limit only bounds swaps through the direct pool.
+    pub fn _swap_to_usdc(&mut self, token: Address, amount_in: U256, amount_out_minimum: U256, sqrt_price_limit_x96: U256) -> Result<U256, Vec<u8>> {
+        let path = self.paths_to_usdc.getter(token).get_bytes();
+        if path.is_empty() {
+            let usdc_address = self.usdc_address.get();
+            let fee = self._token_fee(token);
+            return self._swap_tokens(token, usdc_address, fee, amount_in, amount_out_minimum, sqrt_price_limit_x96);
+        }
+        let venue = self._pair_venue(token, self.usdc_address.get());
+        self._swap_path(venue, path, amount_in, amount_out_minimum)
+    }
+
+    /// Swaps `amount_in` USDC to `token`, along its configured path if any.
+    /// The price limit only bounds swaps through the direct pool.
+    pub fn _swap_from_usdc(&mut self, token: Address, amount_in: U256, amount_out_minimum: U256, sqrt_price_limit_x96: U256) -> Result<U256, Vec<u8>> {
+        let path = self.paths_from_usdc.getter(token).get_bytes();
+        if path.is_empty() {
+            let usdc_address = self.usdc_address.get();
+            let fee = self._token_fee(token);
+            return self._swap_tokens(usdc_address, token, fee, amount_in, amount_out_minimum, sqrt_price_limit_x96);
+        }
+        let venue = self._pair_venue(token, self.usdc_address.get());
+        self._swap_path(venue, path, amount_in, amount_out_minimum)
+    }
+
+    /// Swaps `amount_in` of `token_in` to `token_out`. Legs touching USDC
+    /// take the token's USDC route, others the route of `_pair_route`.
+    pub fn _swap_pair(&mut self, token_in: Address, token_out: Address, amount_in: U256, amount_out_minimum: U256, sqrt_price_limit_x96: U256) -> Result<U256, Vec<u8>> {
+        let usdc_address = self.usdc_address.get();
+        if token_out == usdc_address {
+            return self._swap_to_usdc(token_in, amount_in, amount_out_minimum, sqrt_price_limit_x96);
+        }
+        if token_in == usdc_address {
+            return self._swap_from_usdc(token_out, amount_in, amount_out_minimum, sqrt_price_limit_x96);
+        }
+
+        match self._pair_route(token_in, token_out)? {
+            (venue, Some(path)) => self._swap_path(venue, path, amount_in, amount_out_minimum),
+            (_, None) => self._swap_tokens(token_in, token_out, DEFAULT_FEE, amount_in, amount_out_minimum, sqrt_price_limit_x96),
+        }
+    }
+
+    /// Venue and path of a token-to-token swap: the pair's path on the
+    /// pair's venue; a direct pool (`None`) on pair venues other than
+    /// Uniswap V3; else the input's route to USDC joined with the output's
+    /// route from it, on the venue of both USDC pairs. Reverts when the
+    /// USDC pairs are on different venues.
+    pub fn _pair_route(&self, token_in: Address, token_out: Address) -> Result<(Venue, Option<Vec<u8>>), VaultError> {
+        let venue = self._pair_venue(token_in, token_out);
+        let path = self.pair_paths.getter(token_in).getter(token_out).get_bytes();
+        if !path.is_empty() {
+            return Ok((venue, Some(path)));
+        }
+        if venue != Venue::UniswapV3 {
+            return Ok((venue, None));
+        }
+
+        let usdc_address = self.usdc_address.get();
+        let venue = self._pair_venue(token_in, usdc_address);
+        if self._pair_venue(token_out, usdc_address) != venue {
+            return Err(VaultError::InvalidPath(InvalidPath { token: token_out }));
+        }
+        let path = join_paths(&self._usdc_route(token_in, true), &self._usdc_route(token_out, false))
+            .ok_or(VaultError::InvalidPath(InvalidPath { token: token_in }))?;
+        Ok((venue, Some(path)))
+    }
+
+    /// Path `token` is swapped along to USDC (`to_usdc`) or from it: the
+    /// configured path, else the token's direct USDC pool
+    pub fn _usdc_route(&self, token: Address, to_usdc: bool) -> Vec<u8> {
+        let paths = if to_usdc { &self.paths_to_usdc } else { &self.paths_from_usdc };
+        let path = paths.getter(token).get_bytes();
+        if !path.is_empty() {
+            return path;
+        }
+        let usdc_address = self.usdc_address.get();
+        let tokens = if to_usdc { [token, usdc_address] } else { [usdc_address, token] };
+        encode_path(&tokens, &[self._token_fee(token)]).unwrap_or_default()
+    }
+
+    /// Helper function to perform an exact input swap into this contract
     pub fn _swap_tokens(
         &mut self,
         token_in: Address,
         token_out: Address,
         fee: u32,
         amount_in: U256,
-        amount_out_minimum: U256
+        amount_out_minimum: U256,
+        sqrt_price_limit_x96: U256,
     ) -> Result<U256, Vec<u8>> {
-        // Set recipient to this contract
-        let recipient = contract::address();
-        
-        // Set sqrtPriceLimitX96 to 0 (no price limit)
-        let sqrt_price_limit_x96 = U256::ZERO;
-        
         // Call the main swap function
-        self._swap_exact_input(
+        self._swap_exact_input(ExactInputSingle {
             token_in,
             token_out,
             fee,
-            recipient,
+            // Set recipient to this contract
+            recipient: contract::address(),
             amount_in,
             amount_out_minimum,
-            sqrt_price_limit_x96
-        )
+            // Zero for no price limit
+            sqrt_price_limit_x96,
+        })
+    }
+}   
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use alloy_sol_types::{Revert, SolCall, SolError};
+    use mock_host::{calls_to, mock_contract, reset, CALLER, TIMESTAMP};
+    use std::{cell::Cell, rc::Rc};
+
+    const USDC: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+    const RECEIPTS: Address = address!("4ec0000000000000000000000000000000000003");
+    const WETH: Address = address!("82af49447d8a07e3bd95bd0d56f35241523fbab1");
+    const ORACLE: Address = address!("0a0c1e0000000000000000000000000000000004");
+    const ROUTER: Address = address!("4047e40000000000000000000000000000000005");
+
+    /// Fresh vault holding only USDC, administered by `CALLER`
+    fn vault() -> (Vault, Rc<Cell<U256>>) {
+        reset();
+        let usdc = mock_token(USDC, 6);
+        let mut vault = unsafe { Vault::new(U256::ZERO, 0) };
+        vault.initialize(CALLER, USDC, Address::ZERO, vec![USDC]).ok().unwrap();
+        (vault, usdc)
+    }
+
+    /// Fresh vault holding 10,000 USDC and 1 WETH at 3,000 USDC, trading
+    /// through a router whose swaps all revert with "STF"
+    fn trading_vault() -> (Vault, Rc<Cell<U256>>) {
+        reset();
+        mock_token(USDC, 6).set(U256::from(10_000_000_000u64));
+        let weth = mock_token(WETH, 18);
+        weth.set(U256::from(10).pow(U256::from(18)));
+        mock_contract(ORACLE, |_| Ok(IPriceOracle::getPriceCall::abi_encode_returns(&(U256::from(3_000_000_000u64),))));
+        mock_contract(ROUTER, |_| Err(Revert::from("STF").abi_encode()));
+
+        let mut vault = unsafe { Vault::new(U256::ZERO, 0) };
+        vault.initialize(CALLER, USDC, ROUTER, vec![USDC, WETH]).ok().unwrap();
+        vault.set_oracle(ORACLE).ok().unwrap();
+        (vault, weth)
+    }
+
+    /// Sells 0.1 WETH for USDC through `rebalance_pairs`
+    fn sell_weth(vault: &mut Vault) -> Result<RebalanceOutcome, Vec<u8>> {
+        let amount_in = U256::from(10).pow(U256::from(17));
+        vault.rebalance_pairs(vec![WETH], vec![USDC], vec![amount_in], vec![U256::ZERO])
+    }
+
+    /// Stands an ERC-20 in for `token` and returns the vault's balance of it
+    fn mock_token(token: Address, decimals: u8) -> Rc<Cell<U256>> {
+        let balance = Rc::new(Cell::new(U256::ZERO));
+        let held = balance.clone();
+        mock_contract(token, move |data| {
+            let ret = match data[..4].try_into().unwrap() {
+                IERC20::balanceOfCall::SELECTOR => IERC20::balanceOfCall::abi_encode_returns(&(held.get(),)),
+                IERC20::decimalsCall::SELECTOR => IERC20::decimalsCall::abi_encode_returns(&(decimals,)),
+                IERC20::transferFromCall::SELECTOR => {
+                    let call = IERC20::transferFromCall::abi_decode(data, true).unwrap();
+                    held.set(held.get() + call.amount);
+                    IERC20::transferFromCall::abi_encode_returns(&(true,))
+                }
+                IERC20::transferCall::SELECTOR => {
+                    let call = IERC20::transferCall::abi_decode(data, true).unwrap();
+                    held.set(held.get() - call.amount);
+                    IERC20::transferCall::abi_encode_returns(&(true,))
+                }
+                IERC20::approveCall::SELECTOR => IERC20::approveCall::abi_encode_returns(&(true,)),
+                _ => return Err(Vec::new()),
+            };
+            Ok(ret)
+        });
+        balance
+    }
+
+    /// Stands a receipts contract in for `RECEIPTS`, minting receipt 1 to `CALLER`
+    fn mock_receipts() {
+        mock_contract(RECEIPTS, |data| {
+            let ret = match data[..4].try_into().unwrap() {
+                IReceipts::initializeCall::SELECTOR => Vec::new(),
+                IReceipts::mintCall::SELECTOR => IReceipts::mintCall::abi_encode_returns(&(U256::from(1),)),
+                IReceipts::ownerOfCall::SELECTOR => IReceipts::ownerOfCall::abi_encode_returns(&(CALLER,)),
+                IReceipts::burnCall::SELECTOR => Vec::new(),
+                _ => return Err(Vec::new()),
+            };
+            Ok(ret)
+        });
+    }
+
+    #[test]
+    fn names_the_failed_leg_in_atomic_mode() {
+        let (mut vault, _) = trading_vault();
+        vault.set_rebalance_mode(RebalanceMode::Atomic as u8).ok().unwrap();
+
+        let reverted = LegReverted { leg: U256::ZERO, token_in: WETH, token_out: USDC, reason: "STF".into() };
+        assert_eq!(sell_weth(&mut vault), Err(reverted.abi_encode()));
+    }
+
+    #[test]
+    fn lets_anyone_trip_the_breaker() {
+        let (mut vault, weth) = trading_vault();
+        vault.erc20.mint(CALLER, U256::from(13_000_000_000u64)).ok().unwrap();
+        vault.set_max_drawdown(1_000).ok().unwrap();
+        assert_eq!(vault.check_drawdown().ok(), Some(false));
+
+        // Hand every role to someone else, then lose the WETH
+        let other = address!("0000000000000000000000000000000000000bad");
+        vault.admin.set(other);
+        vault.metric_address.set(other);
+        vault.guardian.set(other);
+        weth.set(U256::ZERO);
+
+        assert_eq!(vault.check_drawdown().ok(), Some(true));
+        assert!(vault.circuit_breaker().ok().unwrap().0);
+        assert!(matches!(vault.resume_rebalancing(), Err(VaultError::NotAuthorized(_))));
+    }
+
+    #[test]
+    fn spaces_slices_out_like_rebalances() {
+        let (mut vault, _) = trading_vault();
+        mock_contract(ROUTER, |_| Ok(U256::from(300_000_000u64).to_be_bytes::<32>().to_vec()));
+        vault.set_min_rebalance_interval(3_600).ok().unwrap();
+
+        let amount_in = U256::from(2) * U256::from(10).pow(U256::from(17));
+        let order_id = vault.submit_sliced_order(WETH, USDC, amount_in, 2, 0).unwrap();
+        assert_eq!(vault.execute_slice(order_id), Ok(U256::from(300_000_000u64)));
+
+        let cooldown = RebalanceCooldown { next_at: TIMESTAMP + 3_600 }.abi_encode();
+        assert_eq!(vault.execute_slice(order_id), Err(cooldown.clone()));
+        assert_eq!(sell_weth(&mut vault), Err(cooldown));
+    }
+
+    /// Registers a stop-loss selling half the WETH once it is under 3,500
+    /// USDC, paying a 1% bounty, with a router selling it for `amount_out`
+    fn fired_trigger(vault: &mut Vault, amount_out: u64) -> U256 {
+        mock_contract(ROUTER, move |_| Ok(U256::from(amount_out).to_be_bytes::<32>().to_vec()));
+        vault.set_trigger_bounty(100).ok().unwrap();
+        vault.register_trigger(WETH, 5_000, U256::from(3_500_000_000u64), U256::ZERO).ok().unwrap()
+    }
+
+    #[test]
+    fn pays_the_bounty_out_of_the_proceeds() {
+        let (mut vault, _) = trading_vault();
+        let trigger_id = fired_trigger(&mut vault, 1_500_000_000);
+        assert_eq!(vault.execute_trigger(trigger_id), Ok(U256::from(15_000_000)));
+        let paid = IERC20::transferCall { recipient: CALLER, amount: U256::from(15_000_000) }.abi_encode();
+        assert!(calls_to(USDC).contains(&paid));
+    }
+
+    #[test]
+    fn pays_nothing_for_a_sale_without_proceeds() {
+        let (mut vault, _) = trading_vault();
+        let trigger_id = fired_trigger(&mut vault, 0);
+        assert_eq!(vault.execute_trigger(trigger_id), Err(TriggerFailed { trigger_id }.abi_encode()));
+        assert!(!calls_to(USDC).iter().any(|data| data.starts_with(&IERC20::transferCall::SELECTOR)));
+    }
+
+    #[test]
+    fn holds_the_bounty_to_the_risk_limits() {
+        let (mut vault, _) = trading_vault();
+        let trigger_id = fired_trigger(&mut vault, 1_500_000_000);
+
+        // The mocked sale leaves NAV unchanged, so the bounty is the whole
+        // drop: 15 USDC out of 13,000, or 11 bps
+        vault.set_risk_limits(0, 0, 10).ok().unwrap();
+        let breach = NavDropExceeded { drop_bps: 11, max_nav_drop_bps: 10 };
+        assert_eq!(vault.execute_trigger(trigger_id), Err(breach.abi_encode()));
     }
-}   
\ No newline at end of file
+
+    #[test]
+    fn starts_no_cooldown_or_epoch_without_a_trade() {
+        let (mut vault, _) = trading_vault();
+        vault.set_min_rebalance_interval(3_600).ok().unwrap();
+
+        // Every leg fails
+        let (results, succeeded) = sell_weth(&mut vault).unwrap();
+        assert_eq!(results[0].1, U256::ZERO);
+        assert_eq!(succeeded, U256::ZERO);
+        assert_eq!(vault.last_rebalance_at.get(), U64::ZERO);
+        assert_eq!(vault.current_epoch(), U256::ZERO);
+
+        // No legs at all
+        let no_legs = vault.rebalance_pairs(vec![], vec![], vec![], vec![]);
+        assert_eq!(no_legs, Err(NoLegs {}.abi_encode()));
+        assert_eq!(vault.last_rebalance_at.get(), U64::ZERO);
+
+        // Neither held up the next rebalance, which trades and starts both
+        mock_contract(ROUTER, |_| Ok(U256::from(300_000_000u64).to_be_bytes::<32>().to_vec()));
+        assert_eq!(sell_weth(&mut vault).unwrap().1, U256::from(1));
+        assert_eq!(vault.last_rebalance_at.get(), U64::from(TIMESTAMP));
+        assert_eq!(vault.current_epoch(), U256::from(1));
+    }
+
+    #[test]
+    fn rebalances_for_the_keeper_only() {
+        let (mut vault, _) = trading_vault();
+        mock_contract(ROUTER, |_| Ok(U256::from(300_000_000u64).to_be_bytes::<32>().to_vec()));
+        let amount_in = U256::from(10).pow(U256::from(17));
+        let (results, succeeded) = vault.rebalance(vec![WETH], vec![true], vec![amount_in]).unwrap();
+        assert_eq!(results, vec![(amount_in, U256::from(300_000_000u64))]);
+        assert_eq!(succeeded, U256::from(1));
+
+        let other = address!("0000000000000000000000000000000000000bad");
+        vault.admin.set(other);
+        vault.metric_address.set(other);
+        let denied = vault.rebalance(vec![WETH], vec![true], vec![amount_in]);
+        assert_eq!(denied, Err(NotAuthorized {}.abi_encode()));
+    }
+
+    #[test]
+    fn initializes_its_receipts() {
+        let (mut vault, _) = vault();
+        assert!(matches!(vault.set_receipts_address(RECEIPTS), Err(VaultError::ReceiptsFailed(_))));
+
+        mock_receipts();
+        vault.set_receipts_address(RECEIPTS).ok().unwrap();
+        assert_eq!(vault.receipts_address(), RECEIPTS);
+        assert_eq!(calls_to(RECEIPTS), vec![IReceipts::initializeCall {}.abi_encode()]);
+    }
+
+    #[test]
+    fn rejects_receipts_initialized_elsewhere() {
+        let (mut vault, _) = vault();
+        mock_contract(RECEIPTS, |_| Err(Vec::new()));
+        assert!(matches!(vault.set_receipts_address(RECEIPTS), Err(VaultError::ReceiptsFailed(_))));
+        assert_eq!(vault.receipts_address(), Address::ZERO);
+    }
+
+    #[test]
+    fn burns_emptied_receipts() {
+        let (mut vault, usdc) = vault();
+        mock_receipts();
+        vault.set_receipts_address(RECEIPTS).ok().unwrap();
+        vault.set_accounting_mode(AccountingMode::Receipts as u8).ok().unwrap();
+
+        let amount = U256::from(1_000_000_000);
+        vault.deposit(amount).ok().unwrap();
+        let id = U256::from(1);
+        assert_eq!(vault.receipt(id).ok(), Some((CALLER, amount, amount, TIMESTAMP)));
+
+        // A partial withdrawal keeps the receipt
+        vault.withdraw_from_receipt(id, amount / U256::from(4)).ok().unwrap();
+        assert!(!calls_to(RECEIPTS).contains(&IReceipts::burnCall { tokenId: id }.abi_encode()));
+
+        vault.withdraw_from_receipt(id, amount - amount / U256::from(4)).ok().unwrap();
+        assert!(calls_to(RECEIPTS).contains(&IReceipts::burnCall { tokenId: id }.abi_encode()));
+        assert_eq!(vault.receipt(id).ok(), Some((CALLER, U256::ZERO, U256::ZERO, TIMESTAMP)));
+        assert_eq!(vault.erc20.total_supply(), U256::ZERO);
+        assert_eq!(usdc.get(), U256::ZERO);
+    }
+}
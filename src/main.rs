@@ -1,4 +1,7 @@
 #[cfg(feature = "export-abi")]
 fn main() {
     stylus_erc20::print_abi("MIT-OR-APACHE-2.0", "pragma solidity ^0.8.28;");
-}
\ No newline at end of file
+}
+
+#[cfg(not(feature = "export-abi"))]
+fn main() {}
@@ -0,0 +1,57 @@
+//! Net asset value helpers
+//!
+//! The vault values its basket in USDC base units. Prices come from the
+//! configured oracle as the value of one whole token in USDC base units,
+//! and are combined here with token balances and the share supply.
+
+// Imported packages
+use alloy_primitives::U256;
+
+/// USDC value of `amount` base units of a token with `decimals`, priced at
+/// `price` USDC base units per whole token
+pub fn token_value(amount: U256, price: U256, decimals: u8) -> U256 {
+    amount * price / U256::from(10).pow(U256::from(decimals))
+}
+
+/// USDC value of `shares` out of `supply` when the vault holds `nav`
+pub fn share_value(shares: U256, nav: U256, supply: U256) -> U256 {
+    if supply.is_zero() {
+        return U256::ZERO;
+    }
+    shares * nav / supply
+}
+
+/// Shares out of `supply` worth `value` when the vault holds `nav`,
+/// rounded up so that whoever takes `value` out pays for all of it
+pub fn shares_for_value(value: U256, nav: U256, supply: U256) -> U256 {
+    if nav.is_zero() {
+        return U256::ZERO;
+    }
+    (value * supply).div_ceil(nav)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_tokens_by_decimals() {
+        // 1.5 WETH at 3000 USDC
+        let amount = U256::from(15u64) * U256::from(10).pow(U256::from(17));
+        let price = U256::from(3_000_000_000u64);
+        assert_eq!(token_value(amount, price, 18), U256::from(4_500_000_000u64));
+    }
+
+    #[test]
+    fn values_shares_pro_rata() {
+        assert_eq!(share_value(U256::from(25), U256::from(1_000), U256::from(100)), U256::from(250));
+        assert_eq!(share_value(U256::from(25), U256::from(1_000), U256::ZERO), U256::ZERO);
+    }
+
+    #[test]
+    fn rounds_shares_for_value_up() {
+        assert_eq!(shares_for_value(U256::from(250), U256::from(1_000), U256::from(100)), U256::from(25));
+        assert_eq!(shares_for_value(U256::from(251), U256::from(1_000), U256::from(100)), U256::from(26));
+        assert_eq!(shares_for_value(U256::from(251), U256::ZERO, U256::from(100)), U256::ZERO);
+    }
+}
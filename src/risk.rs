@@ -0,0 +1,144 @@
+//! Portfolio risk limits
+//!
+//! Every rebalance is checked against these limits once its legs have run,
+//! and reverts if it breaches one. A rebalance is only held responsible for
+//! what it changed: a token price-drifting over its weight cap, or cash
+//! already under the buffer, does not block trades that don't make it
+//! worse. Zero disables a limit.
+
+// Imported packages
+use alloy_primitives::U256;
+use crate::tax::BPS_DENOMINATOR;
+use crate::weights::weight_bps;
+
+/// Admin-configured limits, all in bps of NAV
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RiskLimits {
+    /// Least USDC the vault keeps on hand
+    pub min_cash_bps: u16,
+    /// Most value rebalances may trade per epoch
+    pub max_turnover_bps: u16,
+    /// Most NAV a single rebalance may lose
+    pub max_nav_drop_bps: u16,
+}
+
+impl RiskLimits {
+    /// Whether every limit is at most 100%
+    pub fn is_valid(&self) -> bool {
+        self.min_cash_bps <= BPS_DENOMINATOR && self.max_turnover_bps <= BPS_DENOMINATOR && self.max_nav_drop_bps <= BPS_DENOMINATOR
+    }
+}
+
+/// The limit a rebalance broke
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breach {
+    /// A token bought by the rebalance ends above its weight cap
+    MaxWeight { weight_bps: u16, max_weight_bps: u16 },
+    /// The rebalance spent cash below the buffer
+    CashBuffer { cash_bps: u16, min_cash_bps: u16 },
+    /// The epoch's traded value is above the turnover cap
+    Turnover { turnover: U256, max_turnover: U256 },
+    /// NAV fell by more than the rebalance may lose
+    NavDrop { drop_bps: u16, max_nav_drop_bps: u16 },
+}
+
+/// Checks a token the rebalance bought against its `max_weight_bps`
+pub fn check_weight(value: U256, nav: U256, max_weight_bps: u16) -> Result<(), Breach> {
+    let weight_bps = weight_bps(value, nav);
+    if weight_bps > max_weight_bps {
+        return Err(Breach::MaxWeight { weight_bps, max_weight_bps });
+    }
+    Ok(())
+}
+
+/// Checks the cash left after a rebalance that went from `cash_before` to
+/// `cash_after` USDC
+pub fn check_cash(limits: &RiskLimits, cash_before: U256, cash_after: U256, nav: U256) -> Result<(), Breach> {
+    let cash_bps = weight_bps(cash_after, nav);
+    if cash_after < cash_before && cash_bps < limits.min_cash_bps {
+        return Err(Breach::CashBuffer { cash_bps, min_cash_bps: limits.min_cash_bps });
+    }
+    Ok(())
+}
+
+/// Checks the `turnover` traded so far this epoch against `nav`
+pub fn check_turnover(limits: &RiskLimits, turnover: U256, nav: U256) -> Result<(), Breach> {
+    if limits.max_turnover_bps == 0 {
+        return Ok(());
+    }
+    let max_turnover = nav * U256::from(limits.max_turnover_bps) / U256::from(BPS_DENOMINATOR);
+    if turnover > max_turnover {
+        return Err(Breach::Turnover { turnover, max_turnover });
+    }
+    Ok(())
+}
+
+/// Checks how far NAV fell from `nav_before` to `nav_after`
+pub fn check_nav_drop(limits: &RiskLimits, nav_before: U256, nav_after: U256) -> Result<(), Breach> {
+    if limits.max_nav_drop_bps == 0 || nav_after >= nav_before {
+        return Ok(());
+    }
+    let drop_bps = weight_bps(nav_before - nav_after, nav_before);
+    if drop_bps > limits.max_nav_drop_bps {
+        return Err(Breach::NavDrop { drop_bps, max_nav_drop_bps: limits.max_nav_drop_bps });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> RiskLimits {
+        RiskLimits { min_cash_bps: 500, max_turnover_bps: 2_000, max_nav_drop_bps: 100 }
+    }
+
+    #[test]
+    fn validates_limits() {
+        assert!(limits().is_valid());
+        assert!(RiskLimits::default().is_valid());
+        assert!(!RiskLimits { min_cash_bps: BPS_DENOMINATOR + 1, ..limits() }.is_valid());
+    }
+
+    #[test]
+    fn caps_weights() {
+        let nav = U256::from(10_000);
+        assert_eq!(check_weight(U256::from(3_000), nav, 3_000), Ok(()));
+        assert_eq!(check_weight(U256::from(3_001), nav, 3_000), Err(Breach::MaxWeight { weight_bps: 3_001, max_weight_bps: 3_000 }));
+    }
+
+    #[test]
+    fn keeps_a_cash_buffer() {
+        let nav = U256::from(10_000);
+        assert_eq!(check_cash(&limits(), U256::from(1_000), U256::from(500), nav), Ok(()));
+        assert_eq!(
+            check_cash(&limits(), U256::from(1_000), U256::from(499), nav),
+            Err(Breach::CashBuffer { cash_bps: 499, min_cash_bps: 500 })
+        );
+
+        // Raising cash that is still under the buffer is fine
+        assert_eq!(check_cash(&limits(), U256::from(100), U256::from(200), nav), Ok(()));
+    }
+
+    #[test]
+    fn caps_turnover() {
+        let nav = U256::from(10_000);
+        assert_eq!(check_turnover(&limits(), U256::from(2_000), nav), Ok(()));
+        assert_eq!(
+            check_turnover(&limits(), U256::from(2_001), nav),
+            Err(Breach::Turnover { turnover: U256::from(2_001), max_turnover: U256::from(2_000) })
+        );
+        assert_eq!(check_turnover(&RiskLimits::default(), U256::MAX, nav), Ok(()));
+    }
+
+    #[test]
+    fn caps_nav_drops() {
+        let nav = U256::from(10_000);
+        assert_eq!(check_nav_drop(&limits(), nav, U256::from(9_900)), Ok(()));
+        assert_eq!(check_nav_drop(&limits(), nav, U256::from(11_000)), Ok(()));
+        assert_eq!(
+            check_nav_drop(&limits(), nav, U256::from(9_800)),
+            Err(Breach::NavDrop { drop_bps: 200, max_nav_drop_bps: 100 })
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Oracle-derived bounds for swaps
+//!
+//! Rebalance legs are bounded by what the oracle says they should return:
+//! a minimum output `max_slippage_bps` below the oracle quote, and for
+//! single pools a `sqrtPriceLimitX96` the same distance past the oracle
+//! price, so a manipulated pool fails the leg instead of filling it.
+
+// Imported packages
+use alloy_primitives::{Address, U256, U512};
+use crate::tax::BPS_DENOMINATOR;
+
+/// Slippage allowed while none is configured (1%)
+pub const DEFAULT_MAX_SLIPPAGE_BPS: u16 = 100;
+
+/// Highest slippage the admin may allow (50%)
+pub const MAX_SLIPPAGE_BPS: u16 = 5_000;
+
+/// Lowest `sqrtPriceX96` a Uniswap V3 pool can reach, plus one
+const MIN_SQRT_PRICE_LIMIT: U256 = U256::from_limbs([4_295_128_740, 0, 0, 0]);
+
+/// Highest `sqrtPriceX96` a Uniswap V3 pool can reach, minus one
+const MAX_SQRT_PRICE_LIMIT: U256 = U256::from_limbs([0x5d951d5263988d25, 0xefd1fc6a50648849, 0xfffd8963, 0]);
+
+/// Oracle value of a token: `price` USDC base units per `10^decimals` of
+/// its base units
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quote {
+    pub price: U256,
+    pub decimals: u8,
+}
+
+impl Quote {
+    /// USDC itself, worth one base unit per base unit
+    pub const USDC: Quote = Quote { price: U256::from_limbs([1, 0, 0, 0]), decimals: 0 };
+}
+
+/// What `amount_in` of a token quoted at `quote_in` is worth in a token
+/// quoted at `quote_out`, saturating on absurd prices or decimals
+pub fn expected_out(amount_in: U256, quote_in: Quote, quote_out: Quote) -> U256 {
+    if quote_out.price.is_zero() {
+        return U256::ZERO;
+    }
+    let numerator = pow10(quote_out.decimals).and_then(|scale| scale.checked_mul(U512::from(amount_in) * U512::from(quote_in.price)));
+    let denominator = pow10(quote_in.decimals).and_then(|scale| scale.checked_mul(U512::from(quote_out.price)));
+    match (numerator, denominator) {
+        (Some(numerator), Some(denominator)) => U256::saturating_from(numerator / denominator),
+        (None, _) => U256::MAX,
+        (_, None) => U256::ZERO,
+    }
+}
+
+/// `expected` less `slippage_bps`
+pub fn min_amount_out(expected: U256, slippage_bps: u16) -> U256 {
+    expected * U256::from(BPS_DENOMINATOR.saturating_sub(slippage_bps)) / U256::from(BPS_DENOMINATOR)
+}
+
+/// Most an exact output swap may spend when the oracle says it should
+/// cost `expected`: `expected` paid at a price `slippage_bps` worse, so
+/// that the output is at least `min_amount_out` of what was spent
+pub fn max_amount_in(expected: U256, slippage_bps: u16) -> U256 {
+    let kept = BPS_DENOMINATOR.saturating_sub(slippage_bps);
+    if kept == 0 {
+        return U256::MAX;
+    }
+    expected.saturating_mul(U256::from(BPS_DENOMINATOR)).div_ceil(U256::from(kept))
+}
+
+/// `sqrtPriceLimitX96` stopping a single-pool swap from `token_in` to
+/// `token_out` once the pool price is `slippage_bps` worse than the oracle
+/// price. Clamped to the prices a pool can reach.
+pub fn sqrt_price_limit_x96(token_in: Address, quote_in: Quote, token_out: Address, quote_out: Quote, slippage_bps: u16) -> U256 {
+    // Pools price token1 in token0, both in base units, and sort tokens by address
+    let zero_for_one = token_in < token_out;
+    let (quote0, quote1) = if zero_for_one { (quote_in, quote_out) } else { (quote_out, quote_in) };
+    if quote0.price.is_zero() || quote1.price.is_zero() {
+        return U256::ZERO;
+    }
+
+    // Selling token0 pushes the price down, selling token1 pushes it up
+    let bps = U512::from(BPS_DENOMINATOR);
+    let slippage = U512::from(slippage_bps.min(BPS_DENOMINATOR));
+    let factor = if zero_for_one { bps - slippage } else { bps + slippage };
+
+    // price = (price0 / 10^decimals0) / (price1 / 10^decimals1), scaled by 2^192
+    let numerator = pow10(quote1.decimals)
+        .and_then(|scale| scale.checked_mul(U512::from(quote0.price) * factor))
+        .and_then(|numerator| numerator.checked_shl(192));
+    let denominator = pow10(quote0.decimals).and_then(|scale| scale.checked_mul(U512::from(quote1.price) * bps));
+    let limit = match (numerator, denominator) {
+        (Some(numerator), Some(denominator)) => U256::saturating_from(isqrt(numerator / denominator)),
+        (None, _) => MAX_SQRT_PRICE_LIMIT,
+        (_, None) => MIN_SQRT_PRICE_LIMIT,
+    };
+    limit.clamp(MIN_SQRT_PRICE_LIMIT, MAX_SQRT_PRICE_LIMIT)
+}
+
+/// `10^decimals`, unless it overflows
+fn pow10(decimals: u8) -> Option<U512> {
+    U512::from(10).checked_pow(U512::from(decimals))
+}
+
+/// Integer square root, rounded down
+fn isqrt(n: U512) -> U512 {
+    if n.is_zero() {
+        return n;
+    }
+    let mut x = U512::from(1) << n.bit_len().div_ceil(2);
+    loop {
+        let y = (x + n / x) >> 1;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const WETH: Address = address!("82af49447d8a07e3bd95bd0d56f35241523fbab1");
+    const USDC: Address = address!("af88d065e77c8cc2239327c5edb3a432268e5831");
+
+    fn weth() -> Quote {
+        // 3000 USDC per WETH
+        Quote { price: U256::from(3_000_000_000u64), decimals: 18 }
+    }
+
+    #[test]
+    fn quotes_outputs() {
+        let one_weth = U256::from(10).pow(U256::from(18));
+        assert_eq!(expected_out(one_weth, weth(), Quote::USDC), U256::from(3_000_000_000u64));
+        assert_eq!(expected_out(U256::from(1_500_000_000u64), Quote::USDC, weth()), one_weth / U256::from(2));
+        assert_eq!(expected_out(one_weth, weth(), Quote { price: U256::ZERO, decimals: 18 }), U256::ZERO);
+        assert_eq!(expected_out(one_weth, weth(), Quote { price: U256::from(1), decimals: 255 }), U256::MAX);
+    }
+
+    #[test]
+    fn takes_slippage_off_the_quote() {
+        assert_eq!(min_amount_out(U256::from(10_000), 100), U256::from(9_900));
+        assert_eq!(min_amount_out(U256::from(10_000), 0), U256::from(10_000));
+        assert_eq!(min_amount_out(U256::from(10_000), u16::MAX), U256::ZERO);
+    }
+
+    #[test]
+    fn allows_slippage_on_top_of_the_cost() {
+        assert_eq!(max_amount_in(U256::from(9_900), 100), U256::from(10_000));
+        assert_eq!(max_amount_in(U256::from(9_901), 100), U256::from(10_002));
+        assert_eq!(max_amount_in(U256::from(10_000), 0), U256::from(10_000));
+        assert_eq!(max_amount_in(U256::from(10_000), u16::MAX), U256::MAX);
+        assert!(min_amount_out(max_amount_in(U256::from(12_345), 250), 250) >= U256::from(12_345));
+    }
+
+    #[test]
+    fn computes_square_roots() {
+        for n in [0u64, 1, 2, 3, 4, 15, 16, 17, 1 << 40, u64::MAX] {
+            let root = isqrt(U512::from(n));
+            assert!(root * root <= U512::from(n));
+            assert!((root + U512::from(1)) * (root + U512::from(1)) > U512::from(n));
+        }
+    }
+
+    #[test]
+    fn bounds_the_pool_price() {
+        // WETH sorts before USDC here, so WETH is token0 and the pool prices
+        // it in USDC (token1) base units: 3000e6 / 1e18, i.e. sqrtPriceX96 =
+        // sqrt(3e-9) * 2^96
+        assert!(USDC > WETH);
+        let spot = sqrt_price_limit_x96(WETH, weth(), USDC, Quote::USDC, 0);
+        assert_eq!(spot, U256::from(4_339_505_179_874_779_489_431_521u128));
+
+        // Selling WETH (token0) lets the price fall, buying it lets it rise
+        let selling = sqrt_price_limit_x96(WETH, weth(), USDC, Quote::USDC, 100);
+        let buying = sqrt_price_limit_x96(USDC, Quote::USDC, WETH, weth(), 100);
+        assert!(selling < spot && buying > spot);
+
+        // A 1% move in price is about a 0.5% move in its square root
+        let drop = (spot - selling) * U256::from(10_000) / spot;
+        assert_eq!(drop, U256::from(50));
+    }
+
+    #[test]
+    fn clamps_to_reachable_prices() {
+        let tiny = Quote { price: U256::from(1), decimals: 36 };
+        let huge = Quote { price: U256::MAX, decimals: 0 };
+        assert_eq!(sqrt_price_limit_x96(WETH, tiny, USDC, huge, 0), MIN_SQRT_PRICE_LIMIT);
+        assert_eq!(sqrt_price_limit_x96(WETH, huge, USDC, tiny, 0), MAX_SQRT_PRICE_LIMIT);
+        assert_eq!(sqrt_price_limit_x96(WETH, Quote { price: U256::from(1), decimals: 255 }, USDC, weth(), 0), MIN_SQRT_PRICE_LIMIT);
+        assert_eq!(sqrt_price_limit_x96(WETH, weth(), USDC, Quote { price: U256::ZERO, decimals: 6 }, 0), U256::ZERO);
+    }
+}
@@ -0,0 +1,307 @@
+//! Cost basis tracking for vault shares
+//!
+//! Every deposit opens a [`Lot`] recording how many shares were minted and
+//! how much USDC they cost. Lots are consumed first-in-first-out when shares
+//! leave a holder, which gives the realized gain of a withdrawal and lets
+//! basis follow shares that are transferred between holders.
+//!
+//! Holders may opt into automatic withholding: a share of every realized
+//! gain is diverted to the vault's withholding treasury.
+
+// Imported packages
+use alloy_primitives::{Address, U256, U64};
+use crate::nav::share_value;
+use stylus_sdk::{block, prelude::*};
+
+/// Denominator for rates expressed in basis points
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Highest withholding rate a holder may opt into (50%)
+pub const MAX_WITHHOLDING_BPS: u16 = 5_000;
+
+sol_storage! {
+    /// A single tax lot: shares acquired together at the same cost.
+    pub struct Lot {
+        /// Shares still held from this lot
+        uint256 shares;
+        /// USDC cost of the remaining shares
+        uint256 cost;
+        /// Block timestamp at which the shares were acquired
+        uint64 acquired_at;
+    }
+
+    /// CostBasis keeps the open lots of every holder.
+    pub struct CostBasis {
+        /// Maps holders to their lots, oldest first
+        mapping(address => Lot[]) lots;
+        /// Maps holders to the index of their oldest open lot
+        mapping(address => uint256) heads;
+    }
+}
+
+impl CostBasis {
+    /// Opens a new lot of `shares` costing `cost` for `owner`
+    pub fn add_lot(&mut self, owner: Address, shares: U256, cost: U256) {
+        self.push_lot(owner, shares, cost, block::timestamp());
+    }
+
+    /// Removes `shares` from the oldest lots of `owner` and returns the cost
+    /// basis released. Shares not covered by any lot carry a zero basis.
+    pub fn consume(&mut self, owner: Address, shares: U256) -> U256 {
+        let mut released = U256::ZERO;
+        self.consume_with(owner, shares, |_, cost, _| released += cost);
+        released
+    }
+
+    /// Moves `shares` from the oldest lots of `from` to `to`, keeping their
+    /// cost and acquisition time. Returns the cost basis carried over.
+    pub fn carry_over(&mut self, from: Address, to: Address, shares: U256) -> U256 {
+        let mut moved = Vec::new();
+        self.consume_with(from, shares, |taken, cost, acquired_at| {
+            moved.push((taken, cost, acquired_at))
+        });
+
+        let mut carried = U256::ZERO;
+        for (taken, cost, acquired_at) in moved {
+            self.push_lot(to, taken, cost, acquired_at);
+            carried += cost;
+        }
+        carried
+    }
+
+    /// Total cost basis of the open lots of `owner`
+    pub fn basis_of(&self, owner: Address) -> U256 {
+        let lots = self.lots.get(owner);
+        let head = self.heads.get(owner).to::<usize>();
+        let mut basis = U256::ZERO;
+        for i in head..lots.len() {
+            if let Some(lot) = lots.get(i) {
+                basis += lot.cost.get();
+            }
+        }
+        basis
+    }
+
+    /// Open lots of `owner`, oldest first
+    pub fn open_lots(&self, owner: Address) -> Vec<LotInfo> {
+        let lots = self.lots.get(owner);
+        let head = self.heads.get(owner).to::<usize>();
+        let mut open = Vec::new();
+        for i in head..lots.len() {
+            if let Some(lot) = lots.get(i) {
+                open.push(LotInfo {
+                    index: i,
+                    shares: lot.shares.get(),
+                    cost: lot.cost.get(),
+                    acquired_at: lot.acquired_at.get().to::<u64>(),
+                });
+            }
+        }
+        open
+    }
+
+    fn push_lot(&mut self, owner: Address, shares: U256, cost: U256, acquired_at: u64) {
+        if shares.is_zero() {
+            return;
+        }
+        let mut lots = self.lots.setter(owner);
+        let mut lot = lots.grow();
+        lot.shares.set(shares);
+        lot.cost.set(cost);
+        lot.acquired_at.set(U64::from(acquired_at));
+    }
+
+    /// Walks the lots of `owner` oldest first, taking `shares` in total and
+    /// reporting each `(shares, cost, acquired_at)` slice taken to `f`.
+    fn consume_with<F: FnMut(U256, U256, u64)>(&mut self, owner: Address, shares: U256, mut f: F) {
+        let mut head = self.heads.get(owner).to::<usize>();
+        let mut remaining = shares;
+        let mut lots = self.lots.setter(owner);
+
+        while remaining > U256::ZERO && head < lots.len() {
+            let mut lot = match lots.setter(head) {
+                Some(lot) => lot,
+                None => break,
+            };
+            let lot_shares = lot.shares.get();
+            let lot_cost = lot.cost.get();
+            let acquired_at = lot.acquired_at.get().to::<u64>();
+
+            if remaining >= lot_shares {
+                f(lot_shares, lot_cost, acquired_at);
+                lot.shares.set(U256::ZERO);
+                lot.cost.set(U256::ZERO);
+                remaining -= lot_shares;
+                head += 1;
+            } else {
+                let cost = pro_rata_cost(lot_shares, lot_cost, remaining);
+                f(remaining, cost, acquired_at);
+                lot.shares.set(lot_shares - remaining);
+                lot.cost.set(lot_cost - cost);
+                remaining = U256::ZERO;
+            }
+        }
+
+        self.heads.insert(owner, U256::from(head));
+    }
+}
+
+/// How the vault tracks the cost basis of shares
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountingMode {
+    /// Pooled lots per holder, consumed first-in-first-out
+    Lots = 0,
+    /// One ERC-721 receipt per deposit; withdrawals name the receipt
+    Receipts = 1,
+}
+
+impl AccountingMode {
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(Self::Lots),
+            1 => Some(Self::Receipts),
+            _ => None,
+        }
+    }
+}
+
+/// Why shares changed hands in a `transfer_with_memo`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoCode {
+    /// Donee keeps the donor's basis and holding period
+    Gift = 1,
+    /// Seller realizes a gain at NAV, buyer's basis is the NAV value
+    Sale = 2,
+    /// Move between wallets of the same owner, basis is kept
+    InternalMove = 3,
+    /// Heir's basis is stepped up to the NAV value
+    Inheritance = 4,
+}
+
+impl MemoCode {
+    pub fn from_u8(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::Gift),
+            2 => Some(Self::Sale),
+            3 => Some(Self::InternalMove),
+            4 => Some(Self::Inheritance),
+            _ => None,
+        }
+    }
+
+    /// Whether the recipient's basis is reset to the shares' current value
+    /// instead of carrying over the sender's lots
+    pub fn resets_basis(self) -> bool {
+        matches!(self, Self::Sale | Self::Inheritance)
+    }
+}
+
+/// Snapshot of an open lot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LotInfo {
+    /// Position of the lot in the holder's lot list
+    pub index: usize,
+    /// Shares still held from the lot
+    pub shares: U256,
+    /// USDC cost of those shares
+    pub cost: U256,
+    /// Block timestamp at which the shares were acquired
+    pub acquired_at: u64,
+}
+
+/// An open lot currently worth less than it cost
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HarvestableLoss {
+    /// The lot at a loss
+    pub lot: LotInfo,
+    /// Current USDC value of the lot's shares
+    pub value: U256,
+    /// Shares to redeem to realize the loss, including the older lots
+    /// consumed before it
+    pub shares_needed: U256,
+}
+
+/// Lots at an unrealized loss when the vault holds `nav` for `supply` shares
+pub fn harvestable_losses(lots: &[LotInfo], nav: U256, supply: U256) -> Vec<HarvestableLoss> {
+    let mut losses = Vec::new();
+    let mut shares_needed = U256::ZERO;
+    for lot in lots {
+        shares_needed += lot.shares;
+        let value = share_value(lot.shares, nav, supply);
+        if value < lot.cost {
+            losses.push(HarvestableLoss { lot: lot.clone(), value, shares_needed });
+        }
+    }
+    losses
+}
+
+/// Cost of `taken` shares out of a lot of `shares` costing `cost`
+pub fn pro_rata_cost(shares: U256, cost: U256, taken: U256) -> U256 {
+    if shares.is_zero() {
+        return U256::ZERO;
+    }
+    cost * taken / shares
+}
+
+/// Gain realized when `proceeds` are received for shares with `basis`.
+/// Losses realize no gain.
+pub fn realized_gain(proceeds: U256, basis: U256) -> U256 {
+    proceeds.saturating_sub(basis)
+}
+
+/// Amount withheld from `gain` at `rate_bps`
+pub fn withholding_amount(gain: U256, rate_bps: u16) -> U256 {
+    gain * U256::from(rate_bps) / U256::from(BPS_DENOMINATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pro_rata_cost_splits_lots() {
+        let cost = pro_rata_cost(U256::from(400), U256::from(1_000), U256::from(100));
+        assert_eq!(cost, U256::from(250));
+        assert_eq!(pro_rata_cost(U256::ZERO, U256::from(1_000), U256::from(1)), U256::ZERO);
+    }
+
+    #[test]
+    fn losses_realize_no_gain() {
+        assert_eq!(realized_gain(U256::from(120), U256::from(100)), U256::from(20));
+        assert_eq!(realized_gain(U256::from(80), U256::from(100)), U256::ZERO);
+    }
+
+    #[test]
+    fn withholding_applies_rate_to_gain() {
+        assert_eq!(withholding_amount(U256::from(1_000), 2_500), U256::from(250));
+        assert_eq!(withholding_amount(U256::from(1_000), 0), U256::ZERO);
+    }
+
+    #[test]
+    fn memo_codes_round_trip() {
+        for code in 1..=4 {
+            assert_eq!(MemoCode::from_u8(code).map(|memo| memo as u8), Some(code));
+        }
+        assert_eq!(MemoCode::from_u8(0), None);
+        assert!(MemoCode::Sale.resets_basis());
+        assert!(!MemoCode::Gift.resets_basis());
+    }
+
+    #[test]
+    fn harvestable_losses_count_older_lots() {
+        let lot = |index: usize, shares: u64, cost: u64| LotInfo {
+            index,
+            shares: U256::from(shares),
+            cost: U256::from(cost),
+            acquired_at: 0,
+        };
+        let lots = [lot(0, 100, 80), lot(1, 100, 150), lot(2, 50, 40)];
+
+        // Each share is worth 1.2 USDC
+        let losses = harvestable_losses(&lots, U256::from(300), U256::from(250));
+        assert_eq!(losses.len(), 1);
+        assert_eq!(losses[0].lot.index, 1);
+        assert_eq!(losses[0].value, U256::from(120));
+        assert_eq!(losses[0].shares_needed, U256::from(200));
+    }
+}
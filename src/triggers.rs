@@ -0,0 +1,132 @@
+//! Stop-loss and take-profit triggers
+//!
+//! The keeper registers triggers selling a share of the vault's balance of
+//! a token to USDC once its oracle price falls to the stop price or rises
+//! to the take price. Anyone may execute a trigger whose condition holds
+//! and is paid a bounty out of the proceeds, so these orders run even while
+//! the keeper is offline. Triggers fire once.
+
+// Imported packages
+use alloy_primitives::{Address, U16, U256};
+use crate::tax::BPS_DENOMINATOR;
+use stylus_sdk::prelude::*;
+
+/// Highest bounty the admin may set (1% of the proceeds)
+pub const MAX_TRIGGER_BOUNTY_BPS: u16 = 100;
+
+sol_storage! {
+    /// A price-triggered sell order.
+    pub struct PriceTrigger {
+        address token;
+        /// Share of the vault's balance of `token` to sell, in bps
+        uint16 sell_bps;
+        /// Sell at or below this price, zero for no stop-loss
+        uint256 stop_price;
+        /// Sell at or above this price, zero for no take-profit
+        uint256 take_price;
+        bool active;
+    }
+}
+
+/// A trigger read out of storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TriggerState {
+    pub token: Address,
+    pub sell_bps: u16,
+    pub stop_price: U256,
+    pub take_price: U256,
+    pub active: bool,
+}
+
+impl TriggerState {
+    /// Whether the trigger sells something and has a price to fire at,
+    /// with the stop below the take
+    pub fn is_valid(&self) -> bool {
+        let has_price = !self.stop_price.is_zero() || !self.take_price.is_zero();
+        let ordered = self.stop_price.is_zero() || self.take_price.is_zero() || self.stop_price < self.take_price;
+        self.sell_bps > 0 && self.sell_bps <= BPS_DENOMINATOR && has_price && ordered
+    }
+
+    /// Whether the trigger fires at oracle `price`
+    pub fn fires_at(&self, price: U256) -> bool {
+        let stopped = !self.stop_price.is_zero() && price <= self.stop_price;
+        let taken = !self.take_price.is_zero() && price >= self.take_price;
+        stopped || taken
+    }
+
+    /// Amount of a `balance` the trigger sells
+    pub fn sell_amount(&self, balance: U256) -> U256 {
+        balance * U256::from(self.sell_bps) / U256::from(BPS_DENOMINATOR)
+    }
+}
+
+impl PriceTrigger {
+    /// Reads the trigger out of storage
+    pub fn state(&self) -> TriggerState {
+        TriggerState {
+            token: self.token.get(),
+            sell_bps: self.sell_bps.get().to::<u16>(),
+            stop_price: self.stop_price.get(),
+            take_price: self.take_price.get(),
+            active: self.active.get(),
+        }
+    }
+
+    /// Writes a newly registered, active trigger
+    pub fn init(&mut self, trigger: TriggerState) {
+        self.token.set(trigger.token);
+        self.sell_bps.set(U16::from(trigger.sell_bps));
+        self.stop_price.set(trigger.stop_price);
+        self.take_price.set(trigger.take_price);
+        self.active.set(true);
+    }
+}
+
+/// Bounty paid out of `proceeds` at `bounty_bps`
+pub fn bounty(proceeds: U256, bounty_bps: u16) -> U256 {
+    proceeds * U256::from(bounty_bps.min(MAX_TRIGGER_BOUNTY_BPS)) / U256::from(BPS_DENOMINATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(stop_price: u64, take_price: u64) -> TriggerState {
+        TriggerState {
+            token: Address::ZERO,
+            sell_bps: 5_000,
+            stop_price: U256::from(stop_price),
+            take_price: U256::from(take_price),
+            active: true,
+        }
+    }
+
+    #[test]
+    fn validates_triggers() {
+        assert!(trigger(2_500, 4_000).is_valid());
+        assert!(trigger(2_500, 0).is_valid());
+        assert!(trigger(0, 4_000).is_valid());
+        assert!(!trigger(0, 0).is_valid());
+        assert!(!trigger(4_000, 2_500).is_valid());
+        assert!(!TriggerState { sell_bps: 0, ..trigger(2_500, 0) }.is_valid());
+        assert!(!TriggerState { sell_bps: BPS_DENOMINATOR + 1, ..trigger(2_500, 0) }.is_valid());
+    }
+
+    #[test]
+    fn fires_outside_the_band() {
+        let both = trigger(2_500, 4_000);
+        assert!(both.fires_at(U256::from(2_500)) && both.fires_at(U256::from(4_000)));
+        assert!(!both.fires_at(U256::from(3_000)));
+
+        // A missing side never fires, even at a zero price
+        assert!(!trigger(0, 4_000).fires_at(U256::ZERO));
+        assert!(!trigger(2_500, 0).fires_at(U256::MAX));
+    }
+
+    #[test]
+    fn sells_a_share_and_pays_a_capped_bounty() {
+        assert_eq!(trigger(2_500, 0).sell_amount(U256::from(1_001)), U256::from(500));
+        assert_eq!(bounty(U256::from(10_000), 50), U256::from(50));
+        assert_eq!(bounty(U256::from(10_000), u16::MAX), U256::from(100));
+    }
+}
@@ -0,0 +1,141 @@
+//! Sliced (TWAP-style) orders
+//!
+//! A large trade is split into equal slices executed at least `interval`
+//! seconds apart, so a thin pool has time to recover between them. The
+//! keeper submits the parent order; anyone may execute a slice once it is
+//! due, and every slice is bounded by the oracle like a rebalance leg.
+
+// Imported packages
+use alloy_primitives::{Address, U16, U256, U64};
+use stylus_sdk::prelude::*;
+
+sol_storage! {
+    /// A parent order and how far it has been executed.
+    pub struct SlicedOrder {
+        address token_in;
+        address token_out;
+        /// Total amount of `token_in` to sell over every slice
+        uint256 amount_in;
+        /// Amount of `token_out` received so far
+        uint256 amount_out;
+        uint16 slices;
+        uint16 slices_done;
+        /// Least number of seconds between two slices
+        uint64 interval;
+        uint64 last_executed;
+        bool cancelled;
+    }
+}
+
+/// A sliced order read out of storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlicedOrderState {
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub slices: u16,
+    pub slices_done: u16,
+    pub interval: u64,
+    pub last_executed: u64,
+    pub cancelled: bool,
+}
+
+impl SlicedOrderState {
+    /// Whether every slice has run or the order was cancelled
+    pub fn is_closed(&self) -> bool {
+        self.cancelled || self.slices_done >= self.slices
+    }
+
+    /// When the next slice may run; the first one may run straight away
+    pub fn next_slice_at(&self) -> u64 {
+        if self.slices_done == 0 {
+            return 0;
+        }
+        self.last_executed.saturating_add(self.interval)
+    }
+
+    /// Amount of `token_in` the next slice sells. Slices are equal, the
+    /// last one taking what rounding left over.
+    pub fn next_slice_amount(&self) -> U256 {
+        let slice = self.amount_in / U256::from(self.slices.max(1));
+        if self.slices_done + 1 >= self.slices {
+            return self.amount_in - slice * U256::from(self.slices_done);
+        }
+        slice
+    }
+}
+
+impl SlicedOrder {
+    /// Reads the order out of storage
+    pub fn state(&self) -> SlicedOrderState {
+        SlicedOrderState {
+            token_in: self.token_in.get(),
+            token_out: self.token_out.get(),
+            amount_in: self.amount_in.get(),
+            amount_out: self.amount_out.get(),
+            slices: self.slices.get().to::<u16>(),
+            slices_done: self.slices_done.get().to::<u16>(),
+            interval: self.interval.get().to::<u64>(),
+            last_executed: self.last_executed.get().to::<u64>(),
+            cancelled: self.cancelled.get(),
+        }
+    }
+
+    /// Writes a newly submitted order
+    pub fn init(&mut self, token_in: Address, token_out: Address, amount_in: U256, slices: u16, interval: u64) {
+        self.token_in.set(token_in);
+        self.token_out.set(token_out);
+        self.amount_in.set(amount_in);
+        self.slices.set(U16::from(slices));
+        self.interval.set(U64::from(interval));
+    }
+
+    /// Records a slice that returned `amount_out` at `now`
+    pub fn record_slice(&mut self, amount_out: U256, now: u64) {
+        let done = self.slices_done.get() + U16::from(1);
+        self.slices_done.set(done);
+        self.amount_out.set(self.amount_out.get() + amount_out);
+        self.last_executed.set(U64::from(now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(amount_in: u64, slices: u16, slices_done: u16) -> SlicedOrderState {
+        SlicedOrderState {
+            token_in: Address::ZERO,
+            token_out: Address::ZERO,
+            amount_in: U256::from(amount_in),
+            amount_out: U256::ZERO,
+            slices,
+            slices_done,
+            interval: 600,
+            last_executed: 1_000,
+            cancelled: false,
+        }
+    }
+
+    #[test]
+    fn splits_into_equal_slices() {
+        assert_eq!(order(1_000, 3, 0).next_slice_amount(), U256::from(333));
+        assert_eq!(order(1_000, 3, 1).next_slice_amount(), U256::from(333));
+        assert_eq!(order(1_000, 3, 2).next_slice_amount(), U256::from(334));
+        assert_eq!(order(1_000, 1, 0).next_slice_amount(), U256::from(1_000));
+    }
+
+    #[test]
+    fn spaces_slices_out() {
+        assert_eq!(order(1_000, 3, 0).next_slice_at(), 0);
+        assert_eq!(order(1_000, 3, 1).next_slice_at(), 1_600);
+    }
+
+    #[test]
+    fn closes_when_done_or_cancelled() {
+        assert!(!order(1_000, 3, 2).is_closed());
+        assert!(order(1_000, 3, 3).is_closed());
+        assert!(SlicedOrderState { cancelled: true, ..order(1_000, 3, 0) }.is_closed());
+    }
+}
@@ -0,0 +1,136 @@
+//! Target-weight rebalancing
+//!
+//! Given the value of each basket token and target weights of the vault's
+//! NAV, plans the sells and buys that bring every token back to its weight.
+//! Tokens within `tolerance_bps` of their target are left alone, so small
+//! price moves don't trigger trades. Sells are planned in token units and
+//! buys in USDC, which is what each swap spends.
+
+// Imported packages
+use alloy_primitives::U256;
+use crate::tax::BPS_DENOMINATOR;
+
+/// Tolerance band used while none is configured (1% of NAV)
+pub const DEFAULT_WEIGHT_TOLERANCE_BPS: u16 = 100;
+
+/// What the vault holds of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Holding {
+    pub balance: U256,
+    /// USDC value of `balance`
+    pub value: U256,
+}
+
+/// Trades bringing the holdings to their target weights, by holding index
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// Amounts of each overweight token to sell for USDC
+    pub sells: Vec<(usize, U256)>,
+    /// USDC to spend on each underweight token
+    pub buys: Vec<(usize, U256)>,
+}
+
+/// Whether `weights_bps` add up to at most 100%; USDC makes up the rest
+pub fn valid_weights(weights_bps: &[u16]) -> bool {
+    weights_bps.iter().map(|weight| u32::from(*weight)).sum::<u32>() <= u32::from(BPS_DENOMINATOR)
+}
+
+/// Plans the trades taking each holding to `weights_bps` of `nav`, skipping
+/// holdings within `tolerance_bps` of `nav` of their target
+pub fn plan_rebalance(holdings: &[Holding], weights_bps: &[u16], nav: U256, tolerance_bps: u16) -> Plan {
+    let bps = U256::from(BPS_DENOMINATOR);
+    let band = nav * U256::from(tolerance_bps) / bps;
+    let mut plan = Plan::default();
+
+    for (index, (holding, weight)) in holdings.iter().zip(weights_bps).enumerate() {
+        let target = nav * U256::from(*weight) / bps;
+        if holding.value > target + band {
+            // Sell the excess, in proportion to the balance it is worth
+            let excess = holding.value - target;
+            plan.sells.push((index, holding.balance * excess / holding.value));
+        } else if holding.value + band < target {
+            plan.buys.push((index, target - holding.value));
+        }
+    }
+    plan
+}
+
+/// Scales `buys` down pro rata so they spend no more than `available` USDC
+pub fn fit_buys(buys: &mut [(usize, U256)], available: U256) {
+    let total = buys.iter().fold(U256::ZERO, |total, (_, usdc)| total + usdc);
+    if total <= available {
+        return;
+    }
+    for (_, usdc) in buys.iter_mut() {
+        *usdc = *usdc * available / total;
+    }
+}
+
+/// Share of `nav` that `value` makes up, in bps
+pub fn weight_bps(value: U256, nav: U256) -> u16 {
+    if nav.is_zero() {
+        return 0;
+    }
+    (value * U256::from(BPS_DENOMINATOR) / nav).min(U256::from(BPS_DENOMINATOR)).to::<u16>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holding(balance: u64, value: u64) -> Holding {
+        Holding { balance: U256::from(balance), value: U256::from(value) }
+    }
+
+    #[test]
+    fn checks_weights_add_up() {
+        assert!(valid_weights(&[6_000, 4_000]));
+        assert!(valid_weights(&[5_000]));
+        assert!(!valid_weights(&[6_000, 4_001]));
+        assert!(!valid_weights(&[u16::MAX, u16::MAX]));
+    }
+
+    #[test]
+    fn sells_overweight_and_buys_underweight_tokens() {
+        // NAV 10_000: the first token is worth 6_000 against a 40% target,
+        // the second 1_000 against 40%, the rest is USDC
+        let holdings = [holding(3, 6_000), holding(50, 1_000)];
+        let plan = plan_rebalance(&holdings, &[4_000, 4_000], U256::from(10_000), 100);
+
+        // The 2_000 excess is a third of the first token's value, so of its balance
+        assert_eq!(plan.sells, vec![(0, U256::from(1))]);
+        assert_eq!(plan.buys, vec![(1, U256::from(3_000))]);
+
+        let holdings = [holding(6_000, 6_000)];
+        let plan = plan_rebalance(&holdings, &[4_000], U256::from(10_000), 100);
+        assert_eq!(plan.sells, vec![(0, U256::from(2_000))]);
+    }
+
+    #[test]
+    fn leaves_tokens_within_the_band_alone() {
+        let holdings = [holding(4_050, 4_050), holding(3_950, 3_950)];
+        let plan = plan_rebalance(&holdings, &[4_000, 4_000], U256::from(10_000), 100);
+        assert_eq!(plan, Plan::default());
+
+        let plan = plan_rebalance(&holdings, &[4_000, 4_000], U256::from(10_000), 10);
+        assert_eq!(plan.sells, vec![(0, U256::from(50))]);
+        assert_eq!(plan.buys, vec![(1, U256::from(50))]);
+    }
+
+    #[test]
+    fn fits_buys_to_the_usdc_available() {
+        let mut buys = vec![(0, U256::from(300)), (2, U256::from(100))];
+        fit_buys(&mut buys, U256::from(1_000));
+        assert_eq!(buys, vec![(0, U256::from(300)), (2, U256::from(100))]);
+
+        fit_buys(&mut buys, U256::from(200));
+        assert_eq!(buys, vec![(0, U256::from(150)), (2, U256::from(50))]);
+    }
+
+    #[test]
+    fn reports_weights() {
+        assert_eq!(weight_bps(U256::from(2_500), U256::from(10_000)), 2_500);
+        assert_eq!(weight_bps(U256::from(1), U256::ZERO), 0);
+        assert_eq!(weight_bps(U256::from(20_000), U256::from(10_000)), 10_000);
+    }
+}